@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::sg::LayerKind;
+
 pub type Result<T, E = AtlasError> = core::result::Result<T, E>;
 
 #[derive(Error, Debug)]
@@ -16,12 +18,52 @@ pub enum AtlasError {
     #[error("layer index out of bounds: {0} is not within (0, {1})")]
     LayerOutOfBounds(usize, usize),
 
+    #[error("no layer of kind {0:?}")]
+    LayerKindNotFound(LayerKind),
+
+    #[error("no layer below layer {0}")]
+    NoLayerBelow(usize),
+
     #[error("invalid layers for nesting: cannot nest layer {0} within layer {1}")]
-    InvalidLayersForNesting(usize,usize),
+    InvalidLayersForNesting(usize, usize),
+
+    #[error("cyclic nesting: node {0} cannot be nested under node {1}")]
+    CyclicNesting(usize, usize),
+
+    #[error("cyclic cross edge: an edge from node {0} to node {1} would close a cycle")]
+    CyclicCrossEdge(usize, usize),
+
+    #[error("parallel edge: a sparse layer holds at most one edge from node {0} to node {1}")]
+    ParallelEdge(usize, usize),
+
+    #[error("invalid orientation: forward must be non-zero and not parallel to up")]
+    InvalidOrientation,
+
+    #[error("no path from node {0} to node {1}")]
+    NoPath(usize, usize),
 
     #[error("feature '{0}' not found")]
     FeatureNotFound(String),
 
+    #[error("unknown edge description: '{0}'")]
+    UnknownEdgeDescription(String),
+
+    #[error("layer is frozen")]
+    LayerFrozen,
+
     #[error("coordinates are required for this layer")]
     CoordinatesRequired,
+
+    #[error("unsupported file format: '{0}'")]
+    UnsupportedFormat(String),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("json serialization error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[cfg(feature = "bincode")]
+    #[error("bincode serialization error: {0}")]
+    Bincode(#[from] bincode::Error),
 }
@@ -6,4 +6,7 @@ mod update;
 use update::UpdatePipeline;
 
 pub use server::Server;
-pub use sg::{Layer, SceneGraph};
+pub use sg::{
+    CoordinateConvention, EdgeStorage, Layer, LayerKind, MergePolicy, MergeReport, NodeKind,
+    NodeMergeDiff, ObservableSet, Observer, Point, SceneGraph,
+};
@@ -1,15 +1,22 @@
 use crate::error::Result;
-use crate::{UpdatePipeline, sg::SceneGraph};
+use crate::{
+    UpdatePipeline,
+    sg::{Observer, SceneGraph},
+};
 
 #[derive(Default)]
 pub struct Server {
     update_pipeline: UpdatePipeline,
     scene_graph: SceneGraph,
+    observer: Option<Observer>,
 }
 
 impl Server {
     pub fn new() -> Self {
-        Default::default()
+        Self {
+            update_pipeline: UpdatePipeline::new(),
+            ..Default::default()
+        }
     }
 
     pub fn scene_graph(&mut self) -> Result<&mut SceneGraph> {
@@ -19,4 +26,60 @@ impl Server {
     pub fn update(&mut self, update: SceneGraph) {
         self.update_pipeline.push(update);
     }
+
+    /// Set the observer used by [`visible`](Server::visible).
+    pub fn set_observer(&mut self, observer: Observer) {
+        self.observer = Some(observer);
+    }
+
+    /// Flush pending updates and get the subgraph under the specified root node that is visible
+    /// to the current observer. If no observer was set, nothing is culled.
+    pub fn visible(&mut self, root: usize) -> Result<SceneGraph> {
+        let observer = self.observer.unwrap_or_else(Observer::everything);
+        self.scene_graph()?.visible_subgraph(observer, root)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn visible() -> Result<()> {
+        let mut server = Server::new();
+
+        let mut update = SceneGraph::default();
+        let inside = update.new_coordinates(0.0, 0.0, 1.0, vec![]);
+        let outside = update.new_coordinates(0.0, 0.0, -1.0, vec![]);
+        let room = update.new_node(vec![]);
+        let (inside_id, outside_id, room_id) = (inside.id, outside.id, room.id);
+        let layer = update.new_layer();
+        layer.push_node(inside)?;
+        layer.push_node(outside)?;
+        update.new_layer().push_node(room)?;
+        update.nest(inside_id).under(room_id)?;
+        update.nest(outside_id).under(room_id)?;
+        server.update(update);
+
+        // nothing is culled until an observer is set
+        let visible = server.visible(room_id)?;
+        assert!(visible.node(inside_id).is_ok() && visible.node(outside_id).is_ok());
+
+        // observer at the origin looking towards +Z
+        let observer = Observer::from_ypr(
+            Default::default(),
+            0.0,
+            0.0,
+            0.0,
+            35_f32.to_radians(),
+            0.5,
+            5.0,
+        );
+        server.set_observer(observer);
+        let visible = server.visible(room_id)?;
+        assert!(visible.node(inside_id).is_ok());
+        assert!(visible.node(outside_id).is_err());
+
+        Ok(())
+    }
 }
@@ -1,4 +1,27 @@
-use glam::{Quat, Vec3};
+use glam::{Mat3, Quat, Vec3};
+
+use crate::error::{AtlasError, Result};
+
+/// Axis convention of the world space an [`Observer`] is placed in.
+/// It decides which axes yaw, pitch and roll rotate about and what "forward" means.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CoordinateConvention {
+    /// Right-handed with +Y up and +Z forward: yaw: +Y, pitch: +X, roll: +Z.
+    #[default]
+    YUp,
+    /// Right-handed with +Z up and +X forward: yaw: +Z, pitch: +Y, roll: +X.
+    ZUp,
+}
+
+impl CoordinateConvention {
+    /// Rotation taking the Y-up axes onto the axes of this convention.
+    fn basis(self) -> Quat {
+        match self {
+            Self::YUp => Quat::IDENTITY,
+            Self::ZUp => Quat::from_mat3(&Mat3::from_cols(Vec3::Y, Vec3::Z, Vec3::X)),
+        }
+    }
+}
 
 /// Observer represent a Field-of-View cone frustum in 3D space.
 /// The cone is defined by a position, orientation (quaternion),
@@ -16,6 +39,8 @@ use glam::{Quat, Vec3};
 ///  |far  /    volume     \
 ///  ↓    *-----------------* <- Chord with `far` radius
 ///
+/// Observers built with [`from_perspective`](Observer::from_perspective) model a camera-like
+/// rectangular frustum instead, bounded by four side planes and by near and far planes.
 #[derive(Clone, Copy, Debug)]
 pub struct Observer {
     /// Position of the observer/camera in world space.
@@ -24,9 +49,8 @@ pub struct Observer {
     /// Orientation of the observer/camera as a quaternion.
     rotation: Quat,
 
-    /// `half_angle` represents the maximum angle (in radians) from the forward
-    /// direction that is still considered "inside" the field of view.
-    half_angle_cos: f32,
+    /// Shape of the frustum around the forward direction.
+    shape: FrustumShape,
 
     /// Near distance of the frustum. Points closer than this are not
     /// observed by the observer.
@@ -37,6 +61,31 @@ pub struct Observer {
     far: f32,
 }
 
+/// Shape of the frustum of an [`Observer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FrustumShape {
+    /// Cone around the forward direction. `half_angle_cos` is the cosine of the maximum angle
+    /// (in radians) from the forward direction that is still considered "inside" the field of
+    /// view. Near and far are distances from the observer.
+    Cone { half_angle_cos: f32 },
+    /// Rectangular pyramid around the forward direction, given by the tangents of its horizontal
+    /// and vertical half-angles. Near and far are planes at a depth along the forward direction.
+    Box { h_tan: f32, v_tan: f32 },
+}
+
+impl FrustumShape {
+    /// Inward normals of the side planes of a box frustum, in the observer's local frame.
+    fn side_planes(h_tan: f32, v_tan: f32) -> [Vec3; 4] {
+        [
+            Vec3::new(-1.0, 0.0, h_tan),
+            Vec3::new(1.0, 0.0, h_tan),
+            Vec3::new(0.0, -1.0, v_tan),
+            Vec3::new(0.0, 1.0, v_tan),
+        ]
+        .map(Vec3::normalize)
+    }
+}
+
 impl Observer {
     /// Build from yaw/pitch/roll (radians) in a right-handed XYZ system:
     /// yaw: +Y, pitch: +X, roll: +Z. Rotation order: yaw * pitch * roll.
@@ -52,16 +101,123 @@ impl Observer {
         near: f32,
         far: f32,
     ) -> Self {
+        Self::from_ypr_in(
+            CoordinateConvention::YUp,
+            pos,
+            yaw,
+            pitch,
+            roll,
+            half_angle,
+            near,
+            far,
+        )
+    }
+
+    /// Build from yaw/pitch/roll (radians) in the specified coordinate convention.
+    /// The axes yaw, pitch and roll rotate about are those of the convention, see
+    /// [`CoordinateConvention`]; otherwise it behaves like [`from_ypr`](Observer::from_ypr).
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_ypr_in(
+        convention: CoordinateConvention,
+        pos: Vec3,
+        yaw: f32,
+        pitch: f32,
+        roll: f32,
+        half_angle: f32,
+        near: f32,
+        far: f32,
+    ) -> Self {
+        Self {
+            position: pos,
+            rotation: Self::ypr_rotation(convention, yaw, pitch, roll),
+            shape: FrustumShape::Cone {
+                half_angle_cos: half_angle.cos(),
+            },
+            near,
+            far,
+        }
+    }
+
+    /// Build a camera-like rectangular frustum from yaw/pitch/roll (radians) like
+    /// [`from_ypr`](Observer::from_ypr).
+    /// h_fov, v_fov: full horizontal and vertical field of view angles, radians below π.
+    /// near, far: depths of the near and far planes along the forward direction.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_perspective(
+        pos: Vec3,
+        yaw: f32,
+        pitch: f32,
+        roll: f32,
+        h_fov: f32,
+        v_fov: f32,
+        near: f32,
+        far: f32,
+    ) -> Self {
+        Self {
+            position: pos,
+            rotation: Self::ypr_rotation(CoordinateConvention::YUp, yaw, pitch, roll),
+            shape: FrustumShape::Box {
+                h_tan: (h_fov / 2.0).tan(),
+                v_tan: (v_fov / 2.0).tan(),
+            },
+            near,
+            far,
+        }
+    }
+
+    /// Rotation for yaw/pitch/roll (radians) in the specified coordinate convention.
+    fn ypr_rotation(convention: CoordinateConvention, yaw: f32, pitch: f32, roll: f32) -> Quat {
         let r_yaw = Quat::from_rotation_y(yaw);
         let r_pitch = Quat::from_rotation_x(pitch);
         let r_roll = Quat::from_rotation_z(roll);
-        let rot = r_yaw * r_pitch * r_roll;
-        Self {
+        convention.basis() * r_yaw * r_pitch * r_roll
+    }
+
+    /// Build from a forward and an up direction in world space.
+    /// The up direction only needs to be roughly perpendicular to `forward`, it is
+    /// orthogonalized against it (Gram-Schmidt). Returns an error if `forward` is zero or
+    /// parallel to `up`.
+    /// half_angle: radians.
+    /// near, far: distances.
+    pub fn from_forward(
+        pos: Vec3,
+        forward: Vec3,
+        up: Vec3,
+        half_angle: f32,
+        near: f32,
+        far: f32,
+    ) -> Result<Self> {
+        let forward = forward
+            .try_normalize()
+            .ok_or(AtlasError::InvalidOrientation)?;
+        let up = (up - forward * up.dot(forward))
+            .try_normalize()
+            .ok_or(AtlasError::InvalidOrientation)?;
+        // local frame: +X right-hand side, +Y up, +Z forward
+        let rot = Quat::from_mat3(&Mat3::from_cols(up.cross(forward), up, forward));
+        Ok(Self {
             position: pos,
             rotation: rot,
-            half_angle_cos: half_angle.cos(),
+            shape: FrustumShape::Cone {
+                half_angle_cos: half_angle.cos(),
+            },
             near,
             far,
+        })
+    }
+
+    /// An observer that observes every point in space: the cone is opened to a half-angle of π,
+    /// with no near limit and an infinite far limit.
+    /// Useful for tests and "no culling" modes where an observer is still required.
+    pub fn everything() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            shape: FrustumShape::Cone {
+                half_angle_cos: -1.0,
+            },
+            near: 0.0,
+            far: f32::INFINITY,
         }
     }
 
@@ -71,18 +227,206 @@ impl Observer {
         (self.rotation * Vec3::Z).normalize()
     }
 
-    /// Cone-frustum membership test.
+    /// Distance of a point from the observer projected onto the forward direction.
+    /// Points behind the observer have a negative depth. Usable as a sort key for
+    /// near-to-far (or back-to-front) ordering.
+    pub fn depth(&self, p: &Vec3) -> f32 {
+        (p - self.position).dot(self.forward())
+    }
+
+    /// Transform a world space point into the observer's local frame, where the observer sits
+    /// at the origin looking along +Z.
+    pub fn to_local(&self, p: &Vec3) -> Vec3 {
+        self.rotation.inverse() * (p - self.position)
+    }
+
+    /// Euclidean distance of a point from the observer position.
+    pub fn distance(&self, p: &Vec3) -> f32 {
+        p.distance(self.position)
+    }
+
+    /// Volume of the observable region. For a cone, the part of the cone between the near and
+    /// far distances, i.e. a spherical sector of radius `far` minus the one of radius `near`.
+    /// For a rectangular frustum, the part of the pyramid between the near and far planes.
+    pub fn covered_volume(&self) -> f32 {
+        let cubes = self.far.powi(3) - self.near.powi(3);
+        match self.shape {
+            FrustumShape::Cone { half_angle_cos } => {
+                2.0 / 3.0 * std::f32::consts::PI * (1.0 - half_angle_cos) * cubes
+            }
+            FrustumShape::Box { h_tan, v_tan } => 4.0 / 3.0 * h_tan * v_tan * cubes,
+        }
+    }
+
+    /// Frustum membership test.
     pub fn observers(&self, p: &Vec3) -> bool {
-        // vector from observer to point
+        match self.shape {
+            FrustumShape::Cone { half_angle_cos } => {
+                // vector from observer to point
+                let v = p - self.position;
+                // reachability test
+                let d = v.length();
+                if d < self.near || d > self.far {
+                    return false;
+                }
+                // the apex is only reachable with a zero near distance, and has no direction
+                if d == 0.0 {
+                    return true;
+                }
+                let dir = v / d;
+                let cos_theta = dir.dot(self.forward()).clamp(-1.0, 1.0); // both unit
+                cos_theta >= half_angle_cos
+            }
+            FrustumShape::Box { h_tan, v_tan } => {
+                let l = self.to_local(p);
+                l.z >= self.near
+                    && l.z <= self.far
+                    && FrustumShape::side_planes(h_tan, v_tan)
+                        .iter()
+                        .all(|n| n.dot(l) >= 0.0)
+            }
+        }
+    }
+
+    /// Distance of a point from the observer and its angle (radians) from the forward direction.
+    pub(super) fn polar(&self, p: &Vec3) -> (f32, f32) {
         let v = p - self.position;
-        // reachability test
         let d = v.length();
-        if d < self.near || d > self.far || d == 0.0 {
+        if d == 0.0 {
+            return (0.0, 0.0);
+        }
+        (d, (v / d).dot(self.forward()).clamp(-1.0, 1.0).acos())
+    }
+
+    /// How far another observer moved away from this one, as the distance between their
+    /// positions and the angle (radians) between their forward directions.
+    /// Returns `None` if their frustums differ or are not cones.
+    pub(super) fn drift(&self, other: &Observer) -> Option<(f32, f32)> {
+        if !matches!(self.shape, FrustumShape::Cone { .. })
+            || self.shape != other.shape
+            || self.near != other.near
+            || self.far != other.far
+        {
+            return None;
+        }
+        let rotation = self.forward().dot(other.forward()).clamp(-1.0, 1.0).acos();
+        Some((self.position.distance(other.position), rotation))
+    }
+
+    /// Near distance of the frustum.
+    pub(super) fn near(&self) -> f32 {
+        self.near
+    }
+
+    /// Far distance of the frustum.
+    pub(super) fn far(&self) -> f32 {
+        self.far
+    }
+
+    /// Half-angle in radians of the cone, or of the narrowest cone containing a rectangular
+    /// frustum.
+    pub(super) fn half_angle(&self) -> f32 {
+        match self.shape {
+            FrustumShape::Cone { half_angle_cos } => half_angle_cos.acos(),
+            FrustumShape::Box { h_tan, v_tan } => h_tan.hypot(v_tan).atan(),
+        }
+    }
+
+    /// Check whether every point at a distance within `d ± dd` and an angle (radians) from the
+    /// forward direction within `theta ± dtheta` is observed, or whether none is.
+    /// Returns `None` if the ranges straddle the boundary of the frustum, and always for
+    /// rectangular frustums.
+    pub(super) fn observes_polar_range(
+        &self,
+        (d, dd): (f32, f32),
+        (theta, dtheta): (f32, f32),
+    ) -> Option<bool> {
+        if !matches!(self.shape, FrustumShape::Cone { .. }) {
+            return None;
+        }
+        // margin for rounding errors, points this close to the boundary are never decided here
+        const EPS: f32 = 1e-4;
+        let (dd, dtheta) = (dd + EPS, dtheta + EPS);
+        let half_angle = self.half_angle();
+        if d - dd >= self.near && d + dd <= self.far && theta + dtheta <= half_angle {
+            Some(true)
+        } else if d + dd < self.near || d - dd > self.far || theta - dtheta > half_angle {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Check whether an observed point is close to leaving the frustum, i.e. whether it is
+    /// within `angular_tol` (radians) of the cone surface (or side planes) or within `dist_tol`
+    /// of the near or far limits. Points outside the frustum are never on its boundary.
+    pub fn on_boundary(&self, p: &Vec3, angular_tol: f32, dist_tol: f32) -> bool {
+        if !self.observers(p) {
+            return false;
+        }
+        match self.shape {
+            FrustumShape::Cone { half_angle_cos } => {
+                let v = p - self.position;
+                let d = v.length();
+                if d - self.near <= dist_tol || self.far - d <= dist_tol {
+                    return true;
+                }
+                let cos_theta = (v / d).dot(self.forward()).clamp(-1.0, 1.0);
+                half_angle_cos.acos() - cos_theta.acos() <= angular_tol
+            }
+            FrustumShape::Box { h_tan, v_tan } => {
+                let l = self.to_local(p);
+                if l.z - self.near <= dist_tol || self.far - l.z <= dist_tol {
+                    return true;
+                }
+                // the angle between a direction and a plane is the arcsine of its dot product
+                // with the plane normal
+                let dir = l.normalize();
+                FrustumShape::side_planes(h_tan, v_tan)
+                    .iter()
+                    .any(|n| n.dot(dir).clamp(-1.0, 1.0).asin() <= angular_tol)
+            }
+        }
+    }
+
+    /// Frustum vs axis-aligned bounding box intersection test.
+    ///
+    /// The test is conservative: the box is approximated by its bounding sphere, so it never
+    /// returns `false` for a box that has a point inside the frustum, but it may return `true`
+    /// for a box that only comes close to it. This makes it suitable for pruning spatial
+    /// partitions (e.g. octree cells) before running the exact per-point [`observers`] check.
+    ///
+    /// [`observers`]: Observer::observers
+    pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        let center = (min + max) / 2.0;
+        let radius = (max - min).length() / 2.0;
+
+        if let FrustumShape::Box { h_tan, v_tan } = self.shape {
+            // the sphere misses the frustum if it is entirely outside one of its planes
+            let c = self.to_local(&center);
+            return c.z + radius >= self.near
+                && c.z - radius <= self.far
+                && FrustumShape::side_planes(h_tan, v_tan)
+                    .iter()
+                    .all(|n| n.dot(c) >= -radius);
+        }
+
+        // vector from observer to the center of the bounding sphere
+        let v = center - self.position;
+        let d = v.length();
+        // reachability test: sphere entirely beyond far or entirely closer than near
+        if d - radius > self.far || d + radius < self.near {
             return false;
         }
-        let dir = v / d;
-        let cos_theta = dir.dot(self.forward()); // both unit
-        cos_theta >= self.half_angle_cos
+        // observer is inside the bounding sphere, every direction is possible
+        if d <= radius {
+            return true;
+        }
+
+        // the sphere is seen under an angular radius of asin(radius / d) around its center,
+        // so it touches the cone if its center is within half_angle + that radius of forward.
+        let theta = (v / d).dot(self.forward()).clamp(-1.0, 1.0).acos();
+        theta - (radius / d).asin() <= self.half_angle()
     }
 }
 
@@ -119,6 +463,132 @@ mod test {
         }
     }
 
+    #[test]
+    fn z_up_convention() {
+        let half_angle = 35_f32.to_radians();
+        let z_up = |yaw: f32, pitch: f32| {
+            Observer::from_ypr_in(
+                CoordinateConvention::ZUp,
+                Vec3::ZERO,
+                yaw.to_radians(),
+                pitch.to_radians(),
+                0.0,
+                half_angle,
+                0.6,
+                6.0,
+            )
+        };
+
+        // forward is +X, not +Z
+        let cone = z_up(0.0, 0.0);
+        assert!(cone.observers(&Vec3::new(3.0, 0.0, 0.0)));
+        assert!(!cone.observers(&Vec3::new(0.0, 0.0, 3.0)));
+        assert_eq!(cone.depth(&Vec3::new(3.0, 1.0, 1.0)), 3.0);
+
+        // yaw turns about the up axis: from +X towards +Y
+        let cone = z_up(90.0, 0.0);
+        assert!(cone.observers(&Vec3::new(0.0, 3.0, 0.0)));
+        assert!(!cone.observers(&Vec3::new(3.0, 0.0, 0.0)));
+
+        // positive pitch looks down, as in the Y-up convention
+        let cone = z_up(0.0, 90.0);
+        assert!(cone.observers(&Vec3::new(0.0, 0.0, -3.0)));
+
+        // the Y-up convention is the default
+        let y_up = Observer::from_ypr(Vec3::ZERO, 0.0, 0.0, 0.0, half_angle, 0.6, 6.0);
+        assert!(y_up.observers(&Vec3::new(0.0, 0.0, 3.0)));
+        assert_eq!(CoordinateConvention::default(), CoordinateConvention::YUp);
+    }
+
+    #[test]
+    fn from_forward() -> Result<()> {
+        let half_angle = 35_f32.to_radians();
+        let forward = Vec3::new(1.0, 1.0, 0.0);
+        let cone = Observer::from_forward(Vec3::ZERO, forward, Vec3::Z, half_angle, 0.6, 6.0)?;
+        assert!(cone.forward().abs_diff_eq(forward.normalize(), 1e-6));
+        assert!(cone.observers(&Vec3::new(2.0, 2.0, 0.0)));
+        assert!(!cone.observers(&Vec3::new(-2.0, -2.0, 0.0)));
+
+        // matches the yaw/pitch/roll constructor for the default orientation
+        let cone = Observer::from_forward(Vec3::ZERO, Vec3::Z, Vec3::Y, half_angle, 0.6, 6.0)?;
+        let ypr = Observer::from_ypr(Vec3::ZERO, 0.0, 0.0, 0.0, half_angle, 0.6, 6.0);
+        assert!(cone.rotation.abs_diff_eq(ypr.rotation, 1e-6));
+
+        assert!(matches!(
+            Observer::from_forward(Vec3::ZERO, Vec3::ZERO, Vec3::Y, half_angle, 0.6, 6.0),
+            Err(AtlasError::InvalidOrientation)
+        ));
+        assert!(matches!(
+            Observer::from_forward(Vec3::ZERO, Vec3::Y, -Vec3::Y, half_angle, 0.6, 6.0),
+            Err(AtlasError::InvalidOrientation)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn covered_volume() {
+        let narrow = Observer::from_ypr(Vec3::ZERO, 0.0, 0.0, 0.0, 0.3, 0.6, 6.0);
+        let wide = Observer::from_ypr(Vec3::ZERO, 0.0, 0.0, 0.0, 0.6, 0.6, 6.0);
+        assert!(wide.covered_volume() > narrow.covered_volume());
+
+        // a half-angle of π covers the whole shell between near and far
+        let shell = Observer::from_ypr(Vec3::ZERO, 0.0, 0.0, 0.0, std::f32::consts::PI, 1.0, 2.0);
+        let expected = 4.0 / 3.0 * std::f32::consts::PI * (8.0 - 1.0);
+        assert!((shell.covered_volume() - expected).abs() < 1e-3);
+
+        assert_eq!(Observer::everything().covered_volume(), f32::INFINITY);
+    }
+
+    #[test]
+    fn perspective_frustum() {
+        // 90° wide, 60° high, looking along +Z
+        let (h_fov, v_fov) = (90_f32.to_radians(), 60_f32.to_radians());
+        let camera = Observer::from_perspective(Vec3::ZERO, 0.0, 0.0, 0.0, h_fov, v_fov, 0.5, 10.0);
+
+        let pts = [
+            (Vec3::new(0.0, 0.0, 5.0), true),
+            (Vec3::new(4.0, 0.0, 5.0), true), // within the horizontal field of view
+            (Vec3::new(0.0, 4.0, 5.0), false), // beyond the vertical field of view
+            (Vec3::new(4.9, 2.8, 5.0), true), // corner, outside a 45° cone
+            (Vec3::new(0.0, 0.0, 0.4), false), // before the near plane
+            (Vec3::new(9.0, 0.0, 9.9), true), // farther than `far`, but before the far plane
+            (Vec3::new(0.0, 0.0, 10.5), false),
+            (Vec3::new(0.0, 0.0, -5.0), false),
+        ];
+        for (p, is_observable) in pts {
+            assert_eq!(camera.observers(&p), is_observable, "{p}");
+        }
+        let cone = Observer::from_ypr(Vec3::ZERO, 0.0, 0.0, 0.0, h_fov / 2.0, 0.5, 10.0);
+        assert!(!cone.observers(&Vec3::new(4.9, 2.8, 5.0)));
+
+        // turned towards +X
+        let turned = Observer::from_perspective(
+            Vec3::ZERO,
+            90_f32.to_radians(),
+            0.0,
+            0.0,
+            h_fov,
+            v_fov,
+            0.5,
+            10.0,
+        );
+        assert!(turned.observers(&Vec3::new(5.0, 0.0, 4.0)));
+        assert!(!turned.observers(&Vec3::new(0.0, 0.0, 5.0)));
+
+        // tan(45°) * tan(30°) * 4/3 * (10³ - 0.5³)
+        let expected = 4.0 / 3.0 * 30_f32.to_radians().tan() * (1000.0 - 0.125);
+        assert!((camera.covered_volume() - expected).abs() < 1e-2);
+
+        assert!(camera.on_boundary(&Vec3::new(4.9, 0.0, 5.0), 0.05, 0.1));
+        assert!(camera.on_boundary(&Vec3::new(0.0, 0.0, 0.55), 0.05, 0.1));
+        assert!(!camera.on_boundary(&Vec3::new(0.0, 0.0, 5.0), 0.05, 0.1));
+
+        assert!(camera.intersects_aabb(Vec3::new(4.5, 3.0, 5.0), Vec3::new(5.5, 4.0, 6.0)));
+        assert!(!camera.intersects_aabb(Vec3::new(8.0, -0.5, 2.0), Vec3::new(9.0, 0.5, 3.0)));
+        assert!(!camera.intersects_aabb(Vec3::new(-0.5, -0.5, 11.0), Vec3::new(0.5, 0.5, 12.0)));
+    }
+
     #[test]
     fn cone_frustum_no_rotation() {
         // Observer at origin, yaw=30°, pitch=5°, roll=0°
@@ -136,4 +606,89 @@ mod test {
         assert!(cone.observers(&Vec3::new(0.0, 0.0, 1.0)));
         assert!(!cone.observers(&Vec3::new(6.0, 6.0, 6.0)));
     }
+
+    #[test]
+    fn everything() {
+        let all = Observer::everything();
+        let pts = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(-3.0, 2.0, 0.5),
+            Vec3::new(1e6, -1e6, 1e6),
+        ];
+        for p in pts.iter() {
+            assert!(all.observers(p));
+        }
+        assert!(all.intersects_aabb(Vec3::new(5.0, 5.0, -9.0), Vec3::new(6.0, 6.0, -8.0)));
+    }
+
+    #[test]
+    fn distance() {
+        let cone = Observer::from_ypr(Vec3::new(1.0, 0.0, 0.0), 0.0, 0.0, 0.0, 0.5, 0.6, 6.0);
+        assert!((cone.distance(&Vec3::new(1.0, 3.0, 4.0)) - 5.0).abs() < 1e-5);
+        assert_eq!(cone.distance(&Vec3::new(1.0, 0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn on_boundary() {
+        // Observer at origin looking down +Z, half-angle=35°, near=0.6, far=6.0
+        let cone = Observer::from_ypr(Vec3::ZERO, 0.0, 0.0, 0.0, 35_f32.to_radians(), 0.6, 6.0);
+        let at = |angle: f32, dist: f32| {
+            let angle = angle.to_radians();
+            Vec3::new(angle.sin(), 0.0, angle.cos()) * dist
+        };
+        let (angular_tol, dist_tol) = (2_f32.to_radians(), 0.2);
+
+        // just inside the angular edge
+        assert!(cone.on_boundary(&at(34.0, 3.0), angular_tol, dist_tol));
+        // well inside
+        assert!(!cone.on_boundary(&at(20.0, 3.0), angular_tol, dist_tol));
+        // just outside the angular edge
+        assert!(!cone.on_boundary(&at(36.0, 3.0), angular_tol, dist_tol));
+        // close to the near and far limits
+        assert!(cone.on_boundary(&at(0.0, 0.7), angular_tol, dist_tol));
+        assert!(cone.on_boundary(&at(0.0, 5.9), angular_tol, dist_tol));
+        assert!(!cone.on_boundary(&at(0.0, 6.1), angular_tol, dist_tol));
+    }
+
+    #[test]
+    fn depth() {
+        // Observer at origin, looking down +X
+        let pos = Vec3::new(0.0, 0.0, 0.0);
+        let yaw = 90_f32.to_radians();
+        let cone = Observer::from_ypr(pos, yaw, 0.0, 0.0, 35_f32.to_radians(), 0.6, 6.0);
+
+        assert!((cone.depth(&Vec3::new(2.0, 0.0, 0.0)) - 2.0).abs() < 1e-5);
+        // lateral offsets do not contribute to depth
+        assert!((cone.depth(&Vec3::new(2.0, 1.0, 1.0)) - 2.0).abs() < 1e-5);
+        assert!(cone.depth(&Vec3::new(-1.0, 0.0, 0.0)) < 0.0);
+    }
+
+    #[test]
+    fn cone_frustum_aabb() {
+        // Observer at origin looking down +Z
+        let pos = Vec3::new(0.0, 0.0, 0.0);
+
+        // Cone View Frustum: half-angle=35°, near=0.6, far=6.0
+        let half_angle = 35_f32.to_radians();
+        let near = 0.6;
+        let far = 6.0;
+
+        let cone = Observer::from_ypr(pos, 0.0, 0.0, 0.0, half_angle, near, far);
+
+        // fully inside
+        assert!(cone.intersects_aabb(Vec3::new(-0.2, -0.2, 2.0), Vec3::new(0.2, 0.2, 2.4)));
+        // fully outside: behind the observer, beyond far and off to the side
+        assert!(!cone.intersects_aabb(Vec3::new(-0.2, -0.2, -2.4), Vec3::new(0.2, 0.2, -2.0)));
+        assert!(!cone.intersects_aabb(Vec3::new(-0.2, -0.2, 8.0), Vec3::new(0.2, 0.2, 8.4)));
+        assert!(!cone.intersects_aabb(Vec3::new(4.0, -0.2, 1.0), Vec3::new(4.4, 0.2, 1.4)));
+        // fully closer than near
+        assert!(!cone.intersects_aabb(Vec3::new(-0.1, -0.1, 0.1), Vec3::new(0.1, 0.1, 0.2)));
+        // straddling the near plane: the front part of the box is too close, the back is visible
+        let (min, max) = (Vec3::new(-0.1, -0.1, 0.3), Vec3::new(0.1, 0.1, 0.9));
+        assert!(!cone.observers(&min));
+        assert!(cone.observers(&Vec3::new(0.0, 0.0, 0.8)));
+        assert!(cone.intersects_aabb(min, max));
+    }
 }
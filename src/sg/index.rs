@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{PoisonError, RwLock};
+
+use super::{Layer, Node};
+
+/// Location of every node as `(layer, position)` by node ID.
+type Slots = HashMap<usize, (usize, usize)>;
+
+/// Index from node IDs to their `(layer, position)` in the layers of a scene graph.
+///
+/// The index is built lazily and kept up to date across mutations: on the first lookup after a
+/// mutation, every layer is checked in constant time for nodes appended to it, which are added to
+/// the index, while removed or reordered nodes rebuild it. As that check only looks at the ends
+/// of every layer, lookups never trust the index blindly: every hit is checked against the
+/// layers, and a stale hit or a miss rebuilds the index unless it was already rebuilt since the
+/// last mutation, e.g. after a node was renamed through
+/// [`SceneGraph::node_mut`](super::SceneGraph::node_mut).
+#[derive(Default)]
+pub(super) struct NodeIndex {
+    cache: RwLock<Cache>,
+}
+
+#[derive(Default)]
+struct Cache {
+    /// Generation of the scene graph the index was last synced with.
+    synced: Option<u64>,
+    /// Generation of the scene graph the index was last rebuilt for, in which misses are
+    /// trusted.
+    rebuilt: Option<u64>,
+    slots: Slots,
+    /// Indexed nodes of every layer.
+    marks: Vec<Mark>,
+}
+
+/// Number of indexed nodes of a layer, along with the IDs of the first and last of them.
+#[derive(Debug, Clone, Copy, Default)]
+struct Mark {
+    len: usize,
+    first: usize,
+    last: usize,
+}
+
+impl Mark {
+    fn of<T>(nodes: &[Node<T>]) -> Self {
+        match (nodes.first(), nodes.last()) {
+            (Some(first), Some(last)) => Self {
+                len: nodes.len(),
+                first: first.id,
+                last: last.id,
+            },
+            _ => Self::default(),
+        }
+    }
+
+    /// Check whether the indexed nodes are still in place, i.e. nodes were at most appended.
+    fn holds<T>(&self, nodes: &[Node<T>]) -> bool {
+        self.len == 0
+            || nodes.len() >= self.len
+                && nodes[0].id == self.first
+                && nodes[self.len - 1].id == self.last
+    }
+}
+
+impl NodeIndex {
+    /// Get the `(layer, position)` of a node in `layers`.
+    pub(super) fn locate<T>(
+        &self,
+        generation: u64,
+        layers: &[Layer<T>],
+        nid: usize,
+    ) -> Option<(usize, usize)> {
+        {
+            let cache = self.cache.read().unwrap_or_else(PoisonError::into_inner);
+            if cache.synced == Some(generation)
+                && let Some(slot) = cache.find(generation, layers, nid)
+            {
+                return slot;
+            }
+        }
+        let mut cache = self.cache.write().unwrap_or_else(PoisonError::into_inner);
+        cache.sync(generation, layers);
+        cache.find(generation, layers, nid).unwrap_or_else(|| {
+            cache.rebuild(generation, layers);
+            cache.slots.get(&nid).copied()
+        })
+    }
+
+    /// Drop the index, so it is rebuilt on the next lookup.
+    pub(super) fn invalidate(&mut self) {
+        *self.cache.get_mut().unwrap_or_else(PoisonError::into_inner) = Cache::default();
+    }
+}
+
+impl Cache {
+    /// Look a node up, returning `None` if the index cannot be trusted with it, i.e. if it
+    /// points to a different node or misses the node without having been rebuilt in this
+    /// generation.
+    fn find<T>(
+        &self,
+        generation: u64,
+        layers: &[Layer<T>],
+        nid: usize,
+    ) -> Option<Option<(usize, usize)>> {
+        match self.slots.get(&nid) {
+            Some(&(lid, pos)) => layers
+                .get(lid)
+                .and_then(|l| l.nodes.get(pos))
+                .is_some_and(|n| n.id == nid)
+                .then_some(Some((lid, pos))),
+            None => (self.rebuilt == Some(generation)).then_some(None),
+        }
+    }
+
+    /// Bring the index up to date with `layers`, indexing appended nodes only if possible.
+    fn sync<T>(&mut self, generation: u64, layers: &[Layer<T>]) {
+        if self.synced == Some(generation) {
+            return;
+        }
+        self.synced = Some(generation);
+        let moved = layers.len() < self.marks.len()
+            || self
+                .marks
+                .iter()
+                .zip(layers)
+                .any(|(mark, layer)| !mark.holds(&layer.nodes));
+        if moved {
+            return self.rebuild(generation, layers);
+        }
+
+        self.marks.resize(layers.len(), Mark::default());
+        for (lid, (mark, layer)) in self.marks.iter_mut().zip(layers).enumerate() {
+            for (pos, node) in layer.nodes.iter().enumerate().skip(mark.len) {
+                self.slots.insert(node.id, (lid, pos));
+            }
+            *mark = Mark::of(&layer.nodes);
+        }
+    }
+
+    /// Rebuild the index for `layers` from scratch.
+    fn rebuild<T>(&mut self, generation: u64, layers: &[Layer<T>]) {
+        self.slots = layers
+            .iter()
+            .enumerate()
+            .flat_map(|(lid, l)| {
+                l.nodes
+                    .iter()
+                    .enumerate()
+                    .map(move |(pos, n)| (n.id, (lid, pos)))
+            })
+            .collect();
+        self.marks = layers.iter().map(|l| Mark::of(&l.nodes)).collect();
+        self.synced = Some(generation);
+        self.rebuilt = Some(generation);
+    }
+}
+
+/// Clones start without an index, it is rebuilt for them on their first lookup.
+impl Clone for NodeIndex {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Debug for NodeIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeIndex").finish_non_exhaustive()
+    }
+}
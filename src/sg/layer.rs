@@ -1,21 +1,156 @@
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use super::{Edge, Node, Observer};
+use serde::{Deserialize, Serialize};
+
+use super::{Coordinate, Edge, MergePolicy, Node, Observer};
 use crate::error::{AtlasError, Result};
 
 /// A Layer in the Scene Graph containing multiple Nodes and their Edges.
 /// Each Layer is a well-defined Graph structure representing a specific aspect of the scene,
 /// such as semantic relationships or physical connections between objects.
-#[derive(Debug, Clone)]
-pub struct Layer {
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layer<T = ()> {
     /// List of nodes in this layer.
-    pub(super) nodes: Vec<Node>,
+    pub(super) nodes: Vec<Node<T>>,
+    /// Whether the layer is read-only, see [`SceneGraph::freeze_layer`](super::SceneGraph::freeze_layer).
+    #[serde(default)]
+    pub(super) frozen: bool,
+    /// Sequence number of the next edge added to the layer.
+    #[serde(default)]
+    pub(super) next_edge_seq: u64,
+    /// Edge descriptions allowed in the layer, if restricted.
+    #[serde(default)]
+    pub(super) edge_vocabulary: Option<HashSet<String>>,
+    /// What the layer represents, if tagged.
+    #[serde(default)]
+    pub(super) kind: Option<LayerKind>,
+    /// Edges of the layer by source and destination node ID if it uses
+    /// [`EdgeStorage::Sparse`], in which case its nodes hold no edges themselves.
+    #[serde(default, with = "sparse_edges")]
+    pub(super) sparse_edges: Option<SparseEdges>,
+}
+
+/// Edges of a sparse layer by source node ID, then by destination node ID.
+type SparseEdges = HashMap<usize, HashMap<usize, EdgeMeta>>;
+
+/// An edge of a sparse layer, without the source and destination node IDs it is keyed by.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct EdgeMeta {
+    desc: String,
+    weight: Option<f32>,
+    seq: u64,
+}
+
+impl EdgeMeta {
+    /// Split an edge into its endpoints and the rest of it.
+    fn split(edge: Edge) -> ((usize, usize), Self) {
+        let meta = Self {
+            desc: edge.desc,
+            weight: edge.weight,
+            seq: edge.seq,
+        };
+        ((edge.src, edge.dst), meta)
+    }
+
+    /// Get the edge from `src` to `dst` this is the rest of.
+    fn edge(&self, src: usize, dst: usize) -> Edge {
+        Edge {
+            src,
+            dst,
+            desc: self.desc.clone(),
+            seq: self.seq,
+            weight: self.weight,
+        }
+    }
+}
+
+/// How a layer stores its edges, see [`Layer::set_edge_storage`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EdgeStorage {
+    /// Every node holds its outbound edges in [`Node::edges`].
+    #[default]
+    PerNode,
+    /// The layer holds all of its edges in a map by source and destination node, which finds
+    /// and deletes an edge in constant time and spares the endpoints of every edge.
+    /// Suited to dense layers, e.g. fully connected ones.
+    ///
+    /// **The nodes of a sparse layer hold no edges**: [`Node::edges`] is empty for all of them,
+    /// so the edges have to be queried through the layer or the scene graph, e.g. with
+    /// [`Layer::edges_from`], and [`Node::content_eq`] ignores them.
+    /// A sparse layer also holds at most one edge per pair of nodes: adding another edge between
+    /// nodes that are already connected fails with
+    /// [`ParallelEdge`](AtlasError::ParallelEdge), while switching to this storage or merging
+    /// into a sparse layer keeps only the last added of the parallel edges.
+    Sparse,
+}
+
+/// What a layer represents, used to find layers by role rather than by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LayerKind {
+    /// A layer of positioned nodes, e.g. the points of a point cloud.
+    Metric,
+    /// A layer of semantic nodes, e.g. objects or rooms.
+    Semantic,
+}
+
+impl<T> Default for Layer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Outcome of merging a layer into another one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// IDs of the nodes that did not exist and were added.
+    pub added: Vec<usize>,
+    /// IDs of the existing nodes that were merged with their incoming counterpart.
+    pub updated: Vec<usize>,
+    /// Feature changes of every updated node, in the order of `updated`.
+    pub diffs: Vec<NodeMergeDiff>,
+}
+
+/// Feature changes of a node updated by a merge.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeMergeDiff {
+    /// ID of the updated node.
+    pub id: usize,
+    /// Changed features as `(key, old value, new value)`, the old value being `None` for
+    /// features the node did not have.
+    pub changed: Vec<(String, Option<String>, String)>,
+}
+
+/// Nodes of a layer within an observer's field of view, along with what is needed to update the
+/// set cheaply when the observer moves a little, see [`Layer::update_observable`].
+#[derive(Debug, Clone)]
+pub struct ObservableSet {
+    /// Observer the cached positions are relative to.
+    anchor: Observer,
+    /// Representative point of every positioned node along with its distance and angle with
+    /// respect to the anchor, by node ID.
+    polar: HashMap<usize, (Coordinate, f32, f32)>,
+    /// IDs of the observed nodes.
+    ids: HashSet<usize>,
+}
+
+impl ObservableSet {
+    /// IDs of the observed nodes.
+    pub fn ids(&self) -> &HashSet<usize> {
+        &self.ids
+    }
+
+    /// Check whether a node is observed.
+    pub fn contains(&self, id: usize) -> bool {
+        self.ids.contains(&id)
+    }
 }
 
 /// Node Access and Modification
-impl Layer {
+impl<T> Layer<T> {
     /// Get a reference to a node by its ID.
-    pub fn node(&self, id: usize) -> Result<&Node> {
+    pub fn node(&self, id: usize) -> Result<&Node<T>> {
         self.nodes
             .iter()
             .find(|node| node.id == id)
@@ -23,7 +158,7 @@ impl Layer {
     }
 
     /// Get a mutable reference to a node by its ID.
-    pub fn node_mut(&mut self, id: usize) -> Result<&mut Node> {
+    pub fn node_mut(&mut self, id: usize) -> Result<&mut Node<T>> {
         self.nodes
             .iter_mut()
             .find(|node| node.id == id)
@@ -31,23 +166,260 @@ impl Layer {
     }
 
     /// Add a new node to the layer.
-    pub fn push_node(&mut self, node: Node) {
+    /// Returns an error if the layer is frozen.
+    pub fn push_node(&mut self, node: Node<T>) -> Result<()> {
+        self.ensure_unfrozen()?;
         self.nodes.push(node);
+        Ok(())
+    }
+
+    /// Take ownership of all nodes of the layer without cloning them, leaving the layer empty.
+    /// Parent/child links of nodes on adjacent layers that point into this layer are left
+    /// dangling.
+    pub fn take_nodes(&mut self) -> Vec<Node<T>> {
+        let storage = self.edge_storage();
+        // hand the edges of a sparse layer out with their source nodes
+        self.set_edge_storage(EdgeStorage::PerNode);
+        let nodes = std::mem::take(&mut self.nodes);
+        self.set_edge_storage(storage);
+        nodes
+    }
+
+    /// Check, without mutating the layer, whether an edge from source node to destination node
+    /// could be added, i.e. whether the layer is not frozen and both nodes exist in it.
+    /// Returns the error [`add_edge`](Layer::add_edge) would fail with otherwise, except for
+    /// descriptions outside the [vocabulary](Layer::set_edge_vocabulary) which are not checked.
+    pub fn can_add_edge(&self, src: usize, dst: usize) -> Result<()> {
+        self.ensure_unfrozen()?;
+        let _ = self.node(src)?;
+        let _ = self.node(dst)?;
+        Ok(())
+    }
+
+    /// Restrict the edge descriptions of the layer to a known set, e.g. to catch typos in
+    /// relation labels. Adding an edge with any other description fails afterwards; existing
+    /// edges are left as they are.
+    pub fn set_edge_vocabulary(&mut self, allowed: HashSet<String>) {
+        self.edge_vocabulary = Some(allowed);
+    }
+
+    /// Tag the layer with what it represents, see [`SceneGraph::metric_layer`] and
+    /// [`SceneGraph::semantic_layer`].
+    ///
+    /// [`SceneGraph::metric_layer`]: super::SceneGraph::metric_layer
+    /// [`SceneGraph::semantic_layer`]: super::SceneGraph::semantic_layer
+    pub fn set_kind(&mut self, kind: LayerKind) {
+        self.kind = Some(kind);
+    }
+
+    /// What the layer represents, if tagged.
+    pub fn kind(&self) -> Option<LayerKind> {
+        self.kind
+    }
+
+    /// Switch the layer to another [`EdgeStorage`], moving its edges over.
+    /// When switching to [`EdgeStorage::Sparse`], only the last added of the edges between the
+    /// same pair of nodes is kept.
+    pub fn set_edge_storage(&mut self, storage: EdgeStorage) {
+        let mut edges = self.take_edges();
+        match storage {
+            EdgeStorage::Sparse => {
+                edges.sort_by_key(|e| e.seq);
+                let mut sparse = SparseEdges::new();
+                for edge in edges {
+                    let ((src, dst), meta) = EdgeMeta::split(edge);
+                    sparse.entry(src).or_default().insert(dst, meta);
+                }
+                self.sparse_edges = Some(sparse);
+            }
+            EdgeStorage::PerNode => {
+                self.sparse_edges = None;
+                let positions = self
+                    .nodes
+                    .iter()
+                    .enumerate()
+                    .map(|(pos, n)| (n.id, pos))
+                    .collect::<HashMap<_, _>>();
+                for edge in edges {
+                    if let Some(&pos) = positions.get(&edge.src) {
+                        self.nodes[pos].edges.push(edge);
+                    }
+                }
+            }
+        }
+    }
+
+    /// How the layer stores its edges.
+    pub fn edge_storage(&self) -> EdgeStorage {
+        match self.sparse_edges {
+            Some(_) => EdgeStorage::Sparse,
+            None => EdgeStorage::PerNode,
+        }
+    }
+
+    /// Take all edges out of the layer, the edges of every node in order followed by the sparse
+    /// edges in the order they were added, leaving its storage as it is.
+    fn take_edges(&mut self) -> Vec<Edge> {
+        let mut edges = self
+            .nodes
+            .iter_mut()
+            .flat_map(|n| std::mem::take(&mut n.edges))
+            .collect::<Vec<_>>();
+        if let Some(sparse) = &mut self.sparse_edges {
+            let mut sparse = sparse
+                .drain()
+                .flat_map(|(src, out)| out.into_iter().map(move |(dst, m)| m.edge(src, dst)))
+                .collect::<Vec<_>>();
+            sparse.sort_by_key(|e| e.seq);
+            edges.append(&mut sparse);
+        }
+        edges
+    }
+
+    /// Iterate over all edges of the layer, whatever its storage.
+    /// Edges of a sparse layer are put together on the fly, see [`edge_pairs`](Layer::edge_pairs)
+    /// to only get their endpoints.
+    pub(super) fn all_edges(&self) -> impl Iterator<Item = Cow<'_, Edge>> {
+        let sparse = self.sparse_edges.iter().flatten().flat_map(|(&src, out)| {
+            out.iter()
+                .map(move |(&dst, meta)| Cow::Owned(meta.edge(src, dst)))
+        });
+        self.nodes
+            .iter()
+            .flat_map(|n| &n.edges)
+            .map(Cow::Borrowed)
+            .chain(sparse)
+    }
+
+    /// Iterate over the `(src, dst)` pairs of all edges of the layer, whatever its storage.
+    pub(super) fn edge_pairs(&self) -> impl Iterator<Item = (usize, usize)> {
+        let sparse = self
+            .sparse_edges
+            .iter()
+            .flatten()
+            .flat_map(|(&src, out)| out.keys().map(move |&dst| (src, dst)));
+        self.nodes
+            .iter()
+            .flat_map(|n| &n.edges)
+            .map(|e| (e.src, e.dst))
+            .chain(sparse)
+    }
+
+    /// Iterate over the outbound edges of a node of the layer, whatever its storage.
+    pub(super) fn out_edges<'a>(
+        &'a self,
+        node: &'a Node<T>,
+    ) -> impl Iterator<Item = Cow<'a, Edge>> {
+        let sparse = self
+            .sparse_edges
+            .as_ref()
+            .and_then(|edges| edges.get(&node.id))
+            .into_iter()
+            .flatten()
+            .map(|(&dst, meta)| Cow::Owned(meta.edge(node.id, dst)));
+        node.edges.iter().map(Cow::Borrowed).chain(sparse)
+    }
+
+    /// Return an error if the layer is sparse and already has an edge from `src` to `dst`.
+    fn ensure_not_parallel(&self, src: usize, dst: usize) -> Result<()> {
+        match &self.sparse_edges {
+            Some(edges) if edges.get(&src).is_some_and(|out| out.contains_key(&dst)) => {
+                Err(AtlasError::ParallelEdge(src, dst))
+            }
+            _ => Ok(()),
+        }
     }
 
     /// Add an edge from source node to destination node with a description.
-    /// Ensures both source and destination nodes exist in the layer.
+    /// Ensures both source and destination nodes exist in the layer, that it is not frozen and
+    /// that the description is in its [vocabulary](Layer::set_edge_vocabulary), if any.
+    /// Fails with [`ParallelEdge`](AtlasError::ParallelEdge) if the layer uses
+    /// [`EdgeStorage::Sparse`] and the nodes are already connected.
     pub fn add_edge(&mut self, src: usize, dst: usize, desc: &str) -> Result<()> {
-        // Ensure destination node exists
-        let _ = self.node(dst)?;
-        let src_node = self.node_mut(src)?;
-        src_node.edges.push(Edge::new(src, dst, desc));
+        self.push_edge(Edge::new(src, dst, desc))
+    }
+
+    /// Add an edge like [`add_edge`](Layer::add_edge), with a distance or cost as its weight.
+    pub fn add_weighted_edge(
+        &mut self,
+        src: usize,
+        dst: usize,
+        desc: &str,
+        weight: f32,
+    ) -> Result<()> {
+        self.push_edge(Edge::new(src, dst, desc).with_weight(weight))
+    }
+
+    fn push_edge(&mut self, edge: Edge) -> Result<()> {
+        self.can_add_edge(edge.src, edge.dst)?;
+        self.ensure_known_desc(&edge.desc)?;
+        self.ensure_not_parallel(edge.src, edge.dst)?;
+        let edge = Edge {
+            seq: self.next_edge_seq,
+            ..edge
+        };
+        match &mut self.sparse_edges {
+            Some(edges) => {
+                let ((src, dst), meta) = EdgeMeta::split(edge);
+                edges.entry(src).or_default().insert(dst, meta);
+            }
+            None => self.node_mut(edge.src)?.edges.push(edge),
+        }
+        self.next_edge_seq += 1;
+        Ok(())
+    }
+
+    /// Add edges from a source node to each of the destination nodes with the same description,
+    /// reserving the capacity for all of them at once.
+    /// Performs the checks of [`add_edge`](Layer::add_edge) before adding any edge.
+    pub fn add_edges(&mut self, src: usize, dsts: &[usize], desc: &str) -> Result<()> {
+        self.ensure_unfrozen()?;
+        self.ensure_known_desc(desc)?;
+        for dst in dsts {
+            let _ = self.node(*dst)?;
+            self.ensure_not_parallel(src, *dst)?;
+        }
+        let _ = self.node(src)?;
+        if self.sparse_edges.is_some() {
+            let mut seen = HashSet::new();
+            if let Some(dst) = dsts.iter().find(|dst| !seen.insert(**dst)) {
+                return Err(AtlasError::ParallelEdge(src, *dst));
+            }
+        }
+        let seq = self.next_edge_seq;
+        let edges = dsts.iter().enumerate().map(|(i, dst)| Edge {
+            seq: seq + i as u64,
+            ..Edge::new(src, *dst, desc)
+        });
+        match &mut self.sparse_edges {
+            Some(sparse) => {
+                let out = sparse.entry(src).or_default();
+                out.reserve(dsts.len());
+                out.extend(edges.map(|e| (e.dst, EdgeMeta::split(e).1)));
+            }
+            None => {
+                let src_node = self.node_mut(src)?;
+                src_node.reserve_edges(dsts.len());
+                src_node.edges.extend(edges);
+            }
+        }
+        self.next_edge_seq += dsts.len() as u64;
         Ok(())
     }
 
     /// Delete an edge from source node to destination node.
-    /// Returns an error if the edge does not exist.
+    /// Returns an error if the edge does not exist or the layer is frozen.
     pub fn del_edge(&mut self, src: usize, dst: usize) -> Result<()> {
+        self.ensure_unfrozen()?;
+        let _ = self.node(src)?;
+        if let Some(edges) = &mut self.sparse_edges {
+            let out = edges.get_mut(&src).ok_or(AtlasError::EdgeNotFound)?;
+            out.remove(&dst).ok_or(AtlasError::EdgeNotFound)?;
+            if out.is_empty() {
+                edges.remove(&src);
+            }
+            return Ok(());
+        }
         let src_node = self.node_mut(src)?;
         let index = src_node
             .edges
@@ -57,85 +429,487 @@ impl Layer {
         src_node.edges.swap_remove(index);
         Ok(())
     }
+
+    /// Replace the edge from source node to destination node with an edge in the opposite
+    /// direction with a new description, e.g. to turn "supported by" into "supports".
+    /// Returns an error without changing anything if the edge or its destination node does not
+    /// exist, or if the new description is not in the layer's vocabulary.
+    pub fn reverse_edge(&mut self, src: usize, dst: usize, new_desc: &str) -> Result<()> {
+        self.ensure_known_desc(new_desc)?;
+        let _ = self.node(dst)?;
+        self.del_edge(src, dst)?;
+        self.add_edge(dst, src, new_desc)
+    }
 }
 
 /// Query
-impl Layer {
+impl<T> Layer<T> {
     /// Get List of all nodes matching a specific node features.
-    pub fn nodes_having(&self, keys: &[&str]) -> Vec<&Node> {
+    pub fn nodes_having(&self, keys: &[&str]) -> Vec<&Node<T>> {
         self.nodes
             .iter()
-            .filter(|node| keys.iter().all(|key| node.has_feature(key)))
+            .filter(|node| node.has_all_features(keys))
             .collect()
     }
 
-    /// Get List of all nodes matching a specific node features.
-    pub fn nodes_matching(&self, features: &[&super::node::Feature]) -> Vec<&Node> {
+    /// Get List of all nodes lacking the feature `key`.
+    pub fn nodes_missing(&self, key: &str) -> Vec<&Node<T>> {
         self.nodes
             .iter()
-            .filter(|node| features.iter().all(|f| node.match_feature(f)))
+            .filter(|node| !node.has_feature(key))
             .collect()
     }
 
-    /// Get List of all edges matching a specific description.
-    pub fn edges_matching(&self, desc: &str) -> Vec<&Edge> {
+    /// Get List of all nodes having at least `n` features.
+    pub fn nodes_with_min_features(&self, n: usize) -> Vec<&Node<T>> {
+        self.nodes
+            .iter()
+            .filter(|node| node.feature_count() >= n)
+            .collect()
+    }
+
+    /// Get List of all nodes having a numeric feature `key` whose value is within `[min, max]`.
+    pub fn nodes_with_numeric_feature(&self, key: &str, min: f64, max: f64) -> Vec<&Node<T>> {
+        self.nodes
+            .iter()
+            .filter(|node| {
+                node.features
+                    .iter()
+                    .find(|f| f.key() == key)
+                    .and_then(|f| f.as_f64())
+                    .is_some_and(|v| (min..=max).contains(&v))
+            })
+            .collect()
+    }
+
+    /// Get List of all nodes matching a specific node features.
+    pub fn nodes_matching(&self, features: &[&super::node::Feature]) -> Vec<&Node<T>> {
         self.nodes
             .iter()
-            .flat_map(|n| n.edges.iter().filter(|e| e.desc == desc))
+            .filter(|node| features.iter().all(|f| node.match_feature(f)))
             .collect()
     }
 
+    /// Get List of all edges matching a specific description.
+    pub fn edges_matching(&self, desc: &str) -> Vec<Cow<'_, Edge>> {
+        self.all_edges().filter(|e| e.desc == desc).collect()
+    }
+
+    /// Get all edges of the layer in ascending order of weight.
+    /// Edges without a weight are treated as infinitely heavy and come last.
+    pub fn edges_sorted_by_weight(&self) -> Vec<Cow<'_, Edge>> {
+        let mut edges = self.all_edges().collect::<Vec<_>>();
+        edges.sort_by(|a, b| {
+            let a = a.weight.unwrap_or(f32::INFINITY);
+            let b = b.weight.unwrap_or(f32::INFINITY);
+            a.total_cmp(&b)
+        });
+        edges
+    }
+
     /// Get List of all edges from a specific source node.
-    pub fn edges_from(&self, src: usize) -> Vec<&Edge> {
+    pub fn edges_from(&self, src: usize) -> Vec<Cow<'_, Edge>> {
         match self.node(src) {
-            Ok(n) => n.edges.iter().collect(),
+            Ok(n) => self.out_edges(n).collect(),
             Err(_) => Vec::new(),
         }
     }
 
     /// Get List of all edges to a specific destination node.
-    pub fn edges_to(&self, dst: usize) -> Vec<&Edge> {
+    pub fn edges_to(&self, dst: usize) -> Vec<Cow<'_, Edge>> {
+        self.all_edges().filter(|e| e.dst == dst).collect()
+    }
+
+    /// Get the pairs of nodes `(a, b)` having an edge `a -> b` with the given description but
+    /// missing the reverse edge `b -> a` with the same description.
+    /// An empty result means the relation is symmetric within the layer.
+    pub fn is_symmetric(&self, desc: &str) -> Vec<(usize, usize)> {
+        self.edges_matching(desc)
+            .into_iter()
+            .filter(|e| {
+                !self
+                    .edges_from(e.dst)
+                    .iter()
+                    .any(|r| r.dst == e.src && r.desc == desc)
+            })
+            .map(|e| (e.src, e.dst))
+            .collect()
+    }
+
+    /// Get all edges of the layer in the order they were added, see [`Edge::seq`].
+    pub fn edges_in_insertion_order(&self) -> Vec<Cow<'_, Edge>> {
+        let mut edges = self.all_edges().collect::<Vec<_>>();
+        edges.sort_by_key(|e| e.seq);
+        edges
+    }
+
+    /// Get the edges going from a node in `a` to a node in `b`, e.g. the edges of a cut
+    /// between two partitions of the layer.
+    pub fn edges_crossing(&self, a: &HashSet<usize>, b: &HashSet<usize>) -> Vec<Cow<'_, Edge>> {
+        self.all_edges()
+            .filter(|e| a.contains(&e.src) && b.contains(&e.dst))
+            .collect()
+    }
+
+    /// Get the degree distribution of the layer, mapping each total (outbound plus inbound)
+    /// degree to the number of nodes having it.
+    pub fn degree_distribution(&self) -> HashMap<usize, usize> {
+        let mut degrees = self
+            .nodes
+            .iter()
+            .map(|n| (n.id, 0))
+            .collect::<HashMap<_, _>>();
+        for (src, dst) in self.edge_pairs() {
+            for nid in [src, dst] {
+                if let Some(degree) = degrees.get_mut(&nid) {
+                    *degree += 1;
+                }
+            }
+        }
+        let mut distribution = HashMap::new();
+        for degree in degrees.into_values() {
+            *distribution.entry(degree).or_insert(0) += 1;
+        }
+        distribution
+    }
+
+    /// Get the IDs of the nodes along a shortest path from `src` to `dst`, both included,
+    /// following the direction of the edges.
+    /// Fails like [`shortest_path_edges`](Layer::shortest_path_edges).
+    pub fn shortest_path(&self, src: usize, dst: usize) -> Result<Vec<usize>> {
+        let edges = self.shortest_path_edges(src, dst)?;
+        Ok(std::iter::once(src)
+            .chain(edges.iter().map(|e| e.dst))
+            .collect())
+    }
+
+    /// Get the edges along a shortest path from `src` to `dst`, following the direction of the
+    /// edges. The path is empty if `src` and `dst` are the same node.
+    /// Returns [`NodeNotFound`](AtlasError::NodeNotFound) if either node is not in the layer
+    /// and [`NoPath`](AtlasError::NoPath) if `dst` is not reachable from `src`.
+    pub fn shortest_path_edges(&self, src: usize, dst: usize) -> Result<Vec<Cow<'_, Edge>>> {
+        self.node(src)?;
+        self.node(dst)?;
+
+        // breadth-first search, remembering the edge each node was first reached through
+        let mut reached_by = HashMap::<usize, Option<Cow<'_, Edge>>>::from([(src, None)]);
+        let mut queue = VecDeque::from([src]);
+        while let Some(nid) = queue.pop_front() {
+            if nid == dst {
+                break;
+            }
+            for edge in self.out_edges(self.node(nid)?) {
+                if let Entry::Vacant(entry) = reached_by.entry(edge.dst) {
+                    queue.push_back(edge.dst);
+                    entry.insert(Some(edge));
+                }
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut nid = dst;
+        loop {
+            match reached_by.remove(&nid) {
+                None => return Err(AtlasError::NoPath(src, dst)),
+                Some(None) => break,
+                Some(Some(edge)) => {
+                    nid = edge.src;
+                    path.push(edge);
+                }
+            }
+        }
+        path.reverse();
+        Ok(path)
+    }
+
+    /// Get the IDs of nodes that have neither outbound nor inbound edges.
+    pub fn isolated_nodes(&self) -> Vec<usize> {
+        let linked = self
+            .edge_pairs()
+            .flat_map(|(src, dst)| [src, dst])
+            .collect::<HashSet<_>>();
         self.nodes
             .iter()
-            .flat_map(|n| n.edges.iter().filter(|e| e.dst == dst))
+            .filter(|n| !linked.contains(&n.id))
+            .map(|n| n.id)
             .collect()
     }
 
+    /// Get the nodes of the layer ordered by the Morton code (Z-order) of their coordinates, so
+    /// that spatially close nodes tend to be close in the output.
+    /// Coordinates are quantized within the bounding box of the layer's coordinates. Nodes
+    /// without coordinates come last, in layer order.
+    pub fn nodes_z_order(&self) -> Vec<&Node<T>> {
+        let coords = self.nodes.iter().filter_map(|n| n.coordinates);
+        let (min, max) = coords.fold(
+            (
+                Coordinate::splat(f32::INFINITY),
+                Coordinate::splat(f32::NEG_INFINITY),
+            ),
+            |(min, max), c| (min.min(c), max.max(c)),
+        );
+        let extent = (max - min).max(Coordinate::splat(f32::EPSILON));
+
+        let mut nodes = self.nodes.iter().collect::<Vec<_>>();
+        nodes.sort_by_key(|n| {
+            n.coordinates.map_or(u64::MAX, |c| {
+                let q = ((c - min) / extent * MORTON_MAX as f32).as_uvec3();
+                morton_code(
+                    q.x.min(MORTON_MAX),
+                    q.y.min(MORTON_MAX),
+                    q.z.min(MORTON_MAX),
+                )
+            })
+        });
+        nodes
+    }
+
+    /// Get the directed adjacency matrix of the layer.
+    /// Returns the node IDs in layer order along with an N×N matrix where `matrix[i][j]` is true
+    /// if there is an edge from the `i`-th node to the `j`-th node.
+    pub fn adjacency_matrix(&self) -> (Vec<usize>, Vec<Vec<bool>>) {
+        let ids = self.nodes.iter().map(|n| n.id).collect::<Vec<_>>();
+        let index = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect::<HashMap<_, _>>();
+        let mut matrix = vec![vec![false; ids.len()]; ids.len()];
+        for (src, dst) in self.edge_pairs() {
+            if let (Some(&i), Some(&j)) = (index.get(&src), index.get(&dst)) {
+                matrix[i][j] = true;
+            }
+        }
+        (ids, matrix)
+    }
+
+    /// Get the weighted adjacency matrix of the layer.
+    /// Like [`adjacency_matrix`](Layer::adjacency_matrix), except `matrix[i][j]` holds the
+    /// [weight](Edge::weight) of the edge from the `i`-th node to the `j`-th node, or 0 if there
+    /// is none. Edges without a weight count as 1, and the smallest weight is used if there are
+    /// several edges between the same nodes.
+    pub fn weighted_adjacency_matrix(&self) -> (Vec<usize>, Vec<Vec<f32>>) {
+        let ids = self.nodes.iter().map(|n| n.id).collect::<Vec<_>>();
+        let index = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect::<HashMap<_, _>>();
+        let mut weights = vec![vec![None::<f32>; ids.len()]; ids.len()];
+        for e in self.all_edges() {
+            if let (Some(&i), Some(&j)) = (index.get(&e.src), index.get(&e.dst)) {
+                let w = e.weight.unwrap_or(1.0);
+                weights[i][j] = Some(weights[i][j].map_or(w, |c| c.min(w)));
+            }
+        }
+        let matrix = weights
+            .into_iter()
+            .map(|row| row.into_iter().map(|w| w.unwrap_or(0.0)).collect())
+            .collect();
+        (ids, matrix)
+    }
+
+    /// Lazily iterate over the nodes within the observer's field of view.
+    /// Unlike [`observable_nodes`](Layer::observable_nodes), nodes are neither cloned nor have
+    /// their edges pruned. Nodes without a representative point are ignored.
+    pub fn iter_observable(&self, observer: Observer) -> impl Iterator<Item = &Node<T>> {
+        self.nodes.iter().filter(move |n| {
+            n.representative_point()
+                .is_some_and(|p| observer.observers(&p))
+        })
+    }
+
     /// Get a new Layer containing only nodes within the observer's field of view.
-    /// The check is done using the nodes' coordinates and nodes without coordinates are ignored.
-    pub fn observable_nodes(&self, observer: Observer) -> Self {
+    /// The check is done using the nodes' [representative point](Node::representative_point),
+    /// i.e. their point cloud centroid or coordinates, and nodes without either are ignored.
+    pub fn observable_nodes(&self, observer: Observer) -> Self
+    where
+        T: Clone,
+    {
+        self.retain_cloned(|n| {
+            n.representative_point()
+                .is_some_and(|p| observer.observers(&p))
+        })
+    }
+
+    /// Like [`observable_nodes`](Layer::observable_nodes), but nodes without a representative
+    /// point (e.g. semantic nodes in a mixed layer) are kept unconditionally, and only
+    /// positioned nodes outside the observer's field of view are dropped.
+    pub fn observable_nodes_keep_unpositioned(&self, observer: Observer) -> Self
+    where
+        T: Clone,
+    {
+        self.retain_cloned(|n| {
+            n.representative_point()
+                .is_none_or(|p| observer.observers(&p))
+        })
+    }
+
+    /// Get a new Layer containing clones of the nodes satisfying `keep`, with edges to the
+    /// dropped nodes pruned.
+    fn retain_cloned(&self, keep: impl Fn(&Node<T>) -> bool) -> Self
+    where
+        T: Clone,
+    {
         let nodes = self
             .nodes
             .iter()
-            .filter(|n| n.coordinates.is_some())
-            .filter(|n| observer.observers(&n.coordinates.unwrap()))
+            .filter(|n| keep(n))
             .cloned()
-            .collect::<Vec<Node>>();
-        let mut l = Self { nodes };
+            .collect::<Vec<_>>();
+        let mut l = Self {
+            nodes,
+            frozen: false,
+            next_edge_seq: self.next_edge_seq,
+            edge_vocabulary: self.edge_vocabulary.clone(),
+            kind: self.kind,
+            sparse_edges: self.sparse_edges.clone(),
+        };
 
-        // prune edges to out-of-view nodes
+        // prune edges to dropped nodes
         l.prune();
         l
     }
+
+    /// Get the set of nodes within the observer's field of view, to be kept up to date with
+    /// [`update_observable`](Layer::update_observable) as the observer moves.
+    pub fn observable_set(&self, observer: Observer) -> ObservableSet {
+        let mut set = ObservableSet {
+            anchor: observer,
+            polar: HashMap::new(),
+            ids: HashSet::new(),
+        };
+        for node in &self.nodes {
+            if let Some(p) = node.representative_point() {
+                let (d, theta) = observer.polar(&p);
+                set.polar.insert(node.id, (p, d, theta));
+                if observer.observers(&p) {
+                    set.ids.insert(node.id);
+                }
+            }
+        }
+        set
+    }
+
+    /// Update the set of nodes within the field of view of an observer that moved since `prev`
+    /// was computed, giving the same result as [`observable_set`](Layer::observable_set).
+    /// Only the nodes that may have crossed the boundary of the frustum given how far the
+    /// observer moved are tested again, along with nodes that were added or moved since. If the
+    /// observer moved far, turned by more than half of its half-angle or changed its frustum,
+    /// the set is recomputed from scratch instead.
+    pub fn update_observable(&self, prev: &ObservableSet, observer: Observer) -> ObservableSet {
+        let anchor = prev.anchor;
+        let (shift, turn) = match anchor.drift(&observer) {
+            Some((shift, turn))
+                if turn <= anchor.half_angle() / 2.0 && shift <= anchor.far() / 4.0 =>
+            {
+                (shift, turn)
+            }
+            _ => return self.observable_set(observer),
+        };
+
+        let mut set = ObservableSet {
+            anchor,
+            polar: HashMap::with_capacity(prev.polar.len()),
+            ids: HashSet::new(),
+        };
+        for node in &self.nodes {
+            let Some(p) = node.representative_point() else {
+                continue;
+            };
+            let (d, theta, decided) = match prev.polar.get(&node.id) {
+                Some(&(cached, d, theta)) if cached == p => {
+                    // moving by `shift` turns the direction to a point at distance `d` by at
+                    // most asin(shift / d)
+                    let parallax = if shift < d {
+                        (shift / d).asin()
+                    } else {
+                        std::f32::consts::PI
+                    };
+                    let decided =
+                        observer.observes_polar_range((d, shift), (theta, turn + parallax));
+                    (d, theta, decided)
+                }
+                _ => {
+                    let (d, theta) = anchor.polar(&p);
+                    (d, theta, None)
+                }
+            };
+            set.polar.insert(node.id, (p, d, theta));
+            if decided.unwrap_or_else(|| observer.observers(&p)) {
+                set.ids.insert(node.id);
+            }
+        }
+        set
+    }
+
+    /// Get the `(src, dst)` pairs of the edges that [`observable_nodes`](Layer::observable_nodes)
+    /// drops because at least one of their endpoints is outside the observer's field of view.
+    pub fn culled_edges(&self, observer: Observer) -> Vec<(usize, usize)> {
+        let visible = self
+            .iter_observable(observer)
+            .map(|n| n.id)
+            .collect::<HashSet<_>>();
+        self.edge_pairs()
+            .filter(|(src, dst)| !visible.contains(src) || !visible.contains(dst))
+            .collect()
+    }
 }
 
-impl Layer {
+impl<T> Layer<T> {
     /// Merge another layer into this one.
     /// Nodes with the same ID will be merged, while new nodes will be added.
     /// Deleting Nodes and edges is not supported in this operation.
-    pub fn merge(&mut self, l2: Layer) -> std::result::Result<(), AtlasError> {
+    pub fn merge(&mut self, l2: Self) -> std::result::Result<(), AtlasError> {
+        self.merge_with(l2, MergePolicy::default()).map(|_| ())
+    }
+
+    /// Merge another layer into this one, reconciling edges of existing nodes according to
+    /// the given `policy`. Nodes missing from `l2` are left untouched.
+    /// Returns a report of the nodes that were added to and updated in this layer, or an error
+    /// if this layer is frozen.
+    pub fn merge_with(&mut self, mut l2: Self, policy: MergePolicy) -> Result<MergeReport> {
+        self.ensure_unfrozen()?;
+        // nodes are merged along with their edges, so sparse edges are moved to their nodes
+        if self.sparse_edges.is_some() {
+            self.set_edge_storage(EdgeStorage::PerNode);
+            let report = self.merge_with(l2, policy);
+            self.set_edge_storage(EdgeStorage::Sparse);
+            return report;
+        }
+        l2.set_edge_storage(EdgeStorage::PerNode);
+        self.next_edge_seq = self.next_edge_seq.max(l2.next_edge_seq);
+        self.kind = self.kind.or(l2.kind);
+        let mut report = MergeReport::default();
         for node in l2.nodes {
             match self.node_mut(node.id) {
                 Ok(existing_node) => {
-                    existing_node.merge(node)?;
+                    let changed = node
+                        .features
+                        .iter()
+                        .filter_map(|f| {
+                            let old = existing_node.feature(f.key()).ok();
+                            (old != Some(f.value())).then(|| {
+                                let old = old.map(str::to_string);
+                                (f.key().to_string(), old, f.value().to_string())
+                            })
+                        })
+                        .collect();
+                    report.updated.push(node.id);
+                    report.diffs.push(NodeMergeDiff {
+                        id: node.id,
+                        changed,
+                    });
+                    existing_node.merge_with(node, policy)?;
                 }
                 Err(AtlasError::NodeNotFound) => {
-                    self.push_node(node.clone());
+                    report.added.push(node.id);
+                    self.push_node(node)?;
                 }
                 Err(e) => return Err(e),
             }
         }
-        Ok(())
+        Ok(report)
     }
 
     /// Prune edges that point to non-existing nodes in the layer.
@@ -144,16 +918,73 @@ impl Layer {
         self.nodes
             .iter_mut()
             .for_each(|n| n.edges.retain(|e| node_ids.contains(&e.dst)));
+        if let Some(edges) = &mut self.sparse_edges {
+            let node_ids = node_ids.into_iter().collect::<HashSet<_>>();
+            edges.retain(|src, out| {
+                out.retain(|dst, _| node_ids.contains(dst));
+                node_ids.contains(src) && !out.is_empty()
+            });
+        }
     }
 }
 
-impl Layer {
-    pub(super) fn new() -> Self {
-        Self { nodes: Vec::new() }
+/// Largest quantized coordinate used in Morton codes, i.e. 10 bits per axis.
+const MORTON_MAX: u32 = (1 << 10) - 1;
+
+/// Interleave the lowest 10 bits of the quantized coordinates into a Morton code.
+fn morton_code(x: u32, y: u32, z: u32) -> u64 {
+    (0..10).fold(0, |code, bit| {
+        code | (((x >> bit) & 1) as u64) << (3 * bit)
+            | (((y >> bit) & 1) as u64) << (3 * bit + 1)
+            | (((z >> bit) & 1) as u64) << (3 * bit + 2)
+    })
+}
+
+impl<T> Layer<T> {
+    /// Create a new empty layer.
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            frozen: false,
+            next_edge_seq: 0,
+            edge_vocabulary: None,
+            kind: None,
+            sparse_edges: None,
+        }
+    }
+
+    /// Check whether the layer holds no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Check whether the layer is frozen, i.e. read-only.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Return an error if the layer has an edge vocabulary not containing `desc`.
+    fn ensure_known_desc(&self, desc: &str) -> Result<()> {
+        match &self.edge_vocabulary {
+            Some(allowed) if !allowed.contains(desc) => {
+                Err(AtlasError::UnknownEdgeDescription(desc.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Return an error if the layer is frozen.
+    pub(super) fn ensure_unfrozen(&self) -> Result<()> {
+        if self.frozen {
+            return Err(AtlasError::LayerFrozen);
+        }
+        Ok(())
     }
 
     /// Delete a node by its ID, removing all associated edges in the layer.
-    pub(super) fn del_node(&mut self, id: usize) -> Result<Node> {
+    /// Returns an error if the layer is frozen.
+    pub(super) fn del_node(&mut self, id: usize) -> Result<Node<T>> {
+        self.ensure_unfrozen()?;
         let index = self
             .nodes
             .iter()
@@ -163,6 +994,13 @@ impl Layer {
         self.nodes
             .iter_mut()
             .for_each(|node| node.edges.retain(|edge| edge.dst != id));
+        if let Some(edges) = &mut self.sparse_edges {
+            edges.remove(&id);
+            edges.retain(|_, out| {
+                out.remove(&id);
+                !out.is_empty()
+            });
+        }
         Ok(node)
     }
 
@@ -174,10 +1012,45 @@ impl Layer {
     }
 }
 
+/// Sparse edges are (de)serialized as a list, since maps keyed by pairs are not valid JSON.
+mod sparse_edges {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Edge, EdgeMeta, SparseEdges};
+
+    pub(super) fn serialize<S: Serializer>(
+        edges: &Option<SparseEdges>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let edges = edges.as_ref().map(|edges| {
+            let mut edges = edges
+                .iter()
+                .flat_map(|(&src, out)| out.iter().map(move |(&dst, m)| m.edge(src, dst)))
+                .collect::<Vec<_>>();
+            edges.sort_by_key(|e| e.seq);
+            edges
+        });
+        edges.serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SparseEdges>, D::Error> {
+        let edges = Option::<Vec<Edge>>::deserialize(deserializer)?;
+        Ok(edges.map(|edges| {
+            let mut sparse = SparseEdges::new();
+            for edge in edges {
+                let ((src, dst), meta) = EdgeMeta::split(edge);
+                sparse.entry(src).or_default().insert(dst, meta);
+            }
+            sparse
+        }))
+    }
+}
 
 #[cfg(test)]
 mod test {
-    use super::super::Coordinate;
+    use super::super::{Coordinate, Point};
     use super::*;
 
     fn cone() -> Observer {
@@ -195,6 +1068,229 @@ mod test {
         Observer::from_ypr(pos, yaw, pitch, roll, half_angle, near, far)
     }
 
+    #[test]
+    fn can_add_edge() {
+        let mut layer = Layer::new();
+        for id in 0..2 {
+            layer.push_node(Node::new(id, Vec::new(), None)).unwrap();
+        }
+
+        for (src, dst) in [(0, 1), (1, 1), (0, 2), (2, 0)] {
+            let check = layer.can_add_edge(src, dst);
+            let added = layer.clone().add_edge(src, dst, "next to");
+            assert_eq!(check.is_ok(), added.is_ok());
+            if let (Err(e1), Err(e2)) = (check, added) {
+                assert_eq!(e1.to_string(), e2.to_string());
+            }
+        }
+
+        layer.frozen = true;
+        assert!(matches!(
+            layer.can_add_edge(0, 1),
+            Err(AtlasError::LayerFrozen)
+        ));
+        assert!(layer.node(0).unwrap().edges.is_empty());
+    }
+
+    #[test]
+    fn edge_vocabulary() {
+        let mut layer = Layer::new();
+        for id in 0..2 {
+            layer.push_node(Node::new(id, Vec::new(), None)).unwrap();
+        }
+        layer.add_edge(0, 1, "nxet to").unwrap();
+
+        layer.set_edge_vocabulary(HashSet::from([
+            "next to".to_string(),
+            "supports".to_string(),
+        ]));
+        layer.add_edge(0, 1, "next to").unwrap();
+        assert!(matches!(
+            layer.add_edge(1, 0, "nxet to"),
+            Err(AtlasError::UnknownEdgeDescription(desc)) if desc == "nxet to"
+        ));
+        assert!(matches!(
+            layer.add_edges(1, &[0], "nxet to"),
+            Err(AtlasError::UnknownEdgeDescription(_))
+        ));
+        assert!(matches!(
+            layer.reverse_edge(0, 1, "supported by"),
+            Err(AtlasError::UnknownEdgeDescription(_))
+        ));
+        assert_eq!(layer.node(0).unwrap().edges.len(), 2);
+        assert!(layer.node(1).unwrap().edges.is_empty());
+    }
+
+    #[test]
+    fn add_edges() {
+        let mut layer = Layer::new();
+        for id in 0..4 {
+            layer.push_node(Node::new(id, Vec::new(), None)).unwrap();
+        }
+
+        // reserved edges are pushed without reallocating
+        let mut node = Node::new(9, Vec::new(), None);
+        node.reserve_edges(13);
+        let reserved = node.edges.as_ptr();
+        for dst in 0..13 {
+            node.edges.push(Edge::new(9, dst, "connect"));
+        }
+        assert_eq!(node.edges.as_ptr(), reserved);
+
+        // the space for all edges is reserved up front, rather than grown while pushing
+        let dsts = [0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0];
+        layer.add_edges(0, &dsts, "connect").unwrap();
+        let edges = &layer.node(0).unwrap().edges;
+        assert!(edges.capacity() >= dsts.len());
+        assert_eq!(edges.iter().map(|e| e.dst).collect::<Vec<_>>(), dsts);
+        assert_eq!(
+            edges.iter().map(|e| e.seq()).collect::<Vec<_>>(),
+            (0..dsts.len() as u64).collect::<Vec<_>>()
+        );
+        // the edges are the same as if added one by one
+        let mut one_by_one = Layer::new();
+        for id in 0..4 {
+            one_by_one
+                .push_node(Node::new(id, Vec::new(), None))
+                .unwrap();
+        }
+        for dst in dsts {
+            one_by_one.add_edge(0, dst, "connect").unwrap();
+        }
+        assert_eq!(one_by_one.node(0).unwrap().edges, *edges);
+
+        // nothing is added if a destination is missing
+        assert!(matches!(
+            layer.add_edges(1, &[0, 7], "connect"),
+            Err(AtlasError::NodeNotFound)
+        ));
+        assert!(layer.node(1).unwrap().edges.is_empty());
+    }
+
+    #[test]
+    fn edges_in_insertion_order() {
+        let mut layer = Layer::new();
+        for id in 0..4 {
+            layer.push_node(Node::new(id, Vec::new(), None)).unwrap();
+        }
+        let inserted = [(0, 1), (2, 3), (0, 2), (1, 0), (0, 3), (3, 0)];
+        for (src, dst) in inserted {
+            layer.add_edge(src, dst, "connect").unwrap();
+        }
+        // swap_remove reorders the remaining edges of node 0
+        layer.del_edge(0, 1).unwrap();
+        layer.del_edge(1, 0).unwrap();
+        assert_eq!(
+            layer
+                .node(0)
+                .unwrap()
+                .edges
+                .iter()
+                .map(|e| e.dst)
+                .collect::<Vec<_>>(),
+            [3, 2]
+        );
+
+        let order = |l: &Layer| -> Vec<(usize, usize)> {
+            l.edges_in_insertion_order()
+                .into_iter()
+                .map(|e| (e.src, e.dst))
+                .collect()
+        };
+        let expected = vec![(2, 3), (0, 2), (0, 3), (3, 0)];
+        assert_eq!(order(&layer), expected);
+        let seqs = layer
+            .edges_in_insertion_order()
+            .iter()
+            .map(|e| e.seq())
+            .collect::<Vec<_>>();
+        assert_eq!(seqs, [1, 2, 4, 5]);
+
+        // the order survives serialization and later edges still come last
+        let mut restored: Layer =
+            serde_json::from_str(&serde_json::to_string(&layer).unwrap()).unwrap();
+        restored.add_edge(1, 2, "connect").unwrap();
+        assert_eq!(order(&restored)[..4], expected);
+        assert_eq!(order(&restored)[4], (1, 2));
+    }
+
+    #[test]
+    fn nodes_z_order() {
+        // two tight clusters far apart, interleaved in the layer
+        let pts = [
+            Some(Coordinate::new(0.0, 0.0, 0.0)),
+            Some(Coordinate::new(9.0, 9.0, 9.0)),
+            None,
+            Some(Coordinate::new(0.1, 0.2, 0.0)),
+            Some(Coordinate::new(9.1, 9.0, 8.9)),
+            Some(Coordinate::new(0.2, 0.1, 0.1)),
+            Some(Coordinate::new(8.9, 9.2, 9.0)),
+        ];
+        let mut layer = Layer::new();
+        for (i, p) in pts.iter().enumerate() {
+            layer.push_node(Node::new(i, Vec::new(), *p)).unwrap();
+        }
+
+        let order = layer
+            .nodes_z_order()
+            .iter()
+            .map(|n| n.id)
+            .collect::<Vec<_>>();
+        assert_eq!(order.len(), pts.len());
+        let mut near_origin = order[..3].to_vec();
+        near_origin.sort();
+        let mut far = order[3..6].to_vec();
+        far.sort();
+        assert_eq!(near_origin, [0, 3, 5]);
+        assert_eq!(far, [1, 4, 6]);
+        assert_eq!(order[6], 2);
+
+        assert_eq!(morton_code(1, 0, 0), 0b001);
+        assert_eq!(morton_code(0, 1, 0), 0b010);
+        assert_eq!(morton_code(3, 0, 1), 0b001_101);
+    }
+
+    #[test]
+    fn adjacency_matrix() {
+        let mut layer = Layer::new();
+        for id in [4, 2, 7] {
+            layer.push_node(Node::new(id, Vec::new(), None)).unwrap();
+        }
+        layer.add_edge(4, 2, "next to").unwrap();
+        layer.add_edge(2, 4, "next to").unwrap();
+        layer.add_edge(7, 4, "supported by").unwrap();
+        layer.add_edge(7, 7, "self").unwrap();
+
+        let (ids, matrix) = layer.adjacency_matrix();
+        assert_eq!(ids, vec![4, 2, 7]);
+        assert_eq!(
+            matrix,
+            vec![
+                vec![false, true, false],
+                vec![true, false, false],
+                vec![true, false, true],
+            ]
+        );
+
+        // unweighted edges count as 1, and the lightest of parallel edges is used
+        layer.add_weighted_edge(4, 2, "next to", 2.5).unwrap();
+        layer.add_weighted_edge(4, 7, "next to", 2.5).unwrap();
+        layer.add_weighted_edge(4, 7, "next to", 0.5).unwrap();
+        let (ids, matrix) = layer.weighted_adjacency_matrix();
+        assert_eq!(ids, vec![4, 2, 7]);
+        assert_eq!(
+            matrix,
+            vec![
+                vec![0.0, 1.0, 0.5],
+                vec![1.0, 0.0, 0.0],
+                vec![1.0, 0.0, 1.0],
+            ]
+        );
+
+        let (ids, matrix) = Layer::<()>::new().adjacency_matrix();
+        assert!(ids.is_empty() && matrix.is_empty());
+    }
+
     #[test]
     fn fov_query() {
         let pts = [
@@ -208,10 +1304,12 @@ mod test {
         ];
         let mut layer = Layer::new();
         for (i, p) in pts.iter().enumerate() {
-            layer.push_node(Node::new(i, Vec::new(), Some(*p)));
+            layer.push_node(Node::new(i, Vec::new(), Some(*p))).unwrap();
         }
         // Node with no coordinates
-        layer.push_node(Node::new(pts.len(), Vec::new(), None));
+        layer
+            .push_node(Node::new(pts.len(), Vec::new(), None))
+            .unwrap();
 
         // fully connecting nodes to each other
         for src in 0..layer.nodes.len() {
@@ -235,4 +1333,299 @@ mod test {
             )
         }
     }
+
+    #[test]
+    fn culled_edges() {
+        // nodes 0 and 1 are inside the cone, 2 is outside and 3 has no coordinates
+        let pts = [
+            Some(Coordinate::new(0.0, 0.0, 1.0)),
+            Some(Coordinate::new(0.0, 0.0, 2.0)),
+            Some(Coordinate::new(6.0, 6.0, 6.0)),
+            None,
+        ];
+        let mut layer = Layer::new();
+        for (i, p) in pts.iter().enumerate() {
+            layer.push_node(Node::new(i, Vec::new(), *p)).unwrap();
+        }
+        for src in 0..pts.len() {
+            for dst in 0..pts.len() {
+                layer.add_edge(src, dst, "connect").unwrap();
+            }
+        }
+
+        let culled = layer
+            .culled_edges(cone())
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let expected = (0..pts.len())
+            .flat_map(|src| (0..pts.len()).map(move |dst| (src, dst)))
+            .filter(|(src, dst)| *src >= 2 || *dst >= 2)
+            .collect::<HashSet<_>>();
+        assert_eq!(culled, expected);
+        // the layer is left untouched
+        assert_eq!(layer.edges_matching("connect").len(), 16);
+    }
+
+    #[test]
+    fn observable_nodes_keep_unpositioned() {
+        // node 0 is inside the cone, 1 is outside and 2 has no coordinates
+        let pts = [
+            Some(Coordinate::new(0.0, 0.0, 1.0)),
+            Some(Coordinate::new(6.0, 6.0, 6.0)),
+            None,
+        ];
+        let mut layer = Layer::new();
+        for (i, p) in pts.iter().enumerate() {
+            layer.push_node(Node::new(i, Vec::new(), *p)).unwrap();
+        }
+        layer.add_edges(2, &[0, 1], "contains").unwrap();
+
+        let observed = layer.observable_nodes_keep_unpositioned(cone());
+        assert_eq!(
+            observed.nodes.iter().map(|n| n.id).collect::<Vec<_>>(),
+            [0, 2]
+        );
+        assert_eq!(
+            observed
+                .node(2)
+                .unwrap()
+                .edges
+                .iter()
+                .map(|e| e.dst)
+                .collect::<Vec<_>>(),
+            [0]
+        );
+
+        // the strict variant drops the unpositioned node as well
+        let observed = layer.observable_nodes(cone());
+        assert_eq!(observed.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), [0]);
+    }
+
+    #[test]
+    fn update_observable() {
+        // a 20x20 grid in front of the observer, straddling the cone
+        let mut layer = Layer::new();
+        for i in 0..400 {
+            let (x, y) = ((i % 20) as f32 * 0.4 - 4.0, (i / 20) as f32 * 0.4 - 4.0);
+            let z = 0.3 + (i % 7) as f32;
+            layer
+                .push_node(Node::new(i, Vec::new(), Some(Coordinate::new(x, y, z))))
+                .unwrap();
+        }
+        let observer = |x: f32, yaw: f32| {
+            let half_angle = 35_f32.to_radians();
+            let pos = Coordinate::new(x, 0.0, 0.0);
+            Observer::from_ypr(pos, yaw.to_radians(), 0.0, 0.0, half_angle, 0.6, 6.0)
+        };
+
+        let prev = layer.observable_set(observer(0.0, 0.0));
+        assert!(!prev.ids().is_empty() && prev.ids().len() < 400);
+
+        // a small nudge, then drifting further away from the first observer
+        let mut set = prev.clone();
+        for (x, yaw) in [(0.05, 1.0), (0.1, 3.0), (0.2, 5.0)] {
+            set = layer.update_observable(&set, observer(x, yaw));
+            assert_eq!(set.ids(), layer.observable_set(observer(x, yaw)).ids());
+        }
+
+        // moving far recomputes the set
+        let set = layer.update_observable(&prev, observer(2.0, 40.0));
+        assert_eq!(set.ids(), layer.observable_set(observer(2.0, 40.0)).ids());
+
+        // moved nodes are tested again
+        let mut moved = layer.clone();
+        moved.node_mut(0).unwrap().coordinates = Some(Coordinate::new(0.0, 0.0, 2.0));
+        let set = moved.update_observable(&prev, observer(0.05, 1.0));
+        assert!(set.contains(0));
+    }
+
+    #[test]
+    fn weighted_edges() {
+        let mut layer = Layer::new();
+        for i in 0..3 {
+            layer.push_node(Node::new(i, Vec::new(), None)).unwrap();
+        }
+        layer.add_weighted_edge(0, 1, "road", 2.5).unwrap();
+        layer.add_edge(1, 2, "road").unwrap();
+        layer.add_weighted_edge(1, 0, "road", 0.5).unwrap();
+        layer.add_weighted_edge(2, 0, "road", 1.0).unwrap();
+
+        let order = layer
+            .edges_sorted_by_weight()
+            .iter()
+            .map(|e| (e.src, e.dst, e.weight))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            [
+                (1, 0, Some(0.5)),
+                (2, 0, Some(1.0)),
+                (0, 1, Some(2.5)),
+                (1, 2, None)
+            ]
+        );
+        // weights do not affect the other edge queries
+        assert_eq!(layer.edges_matching("road").len(), 4);
+    }
+
+    #[test]
+    fn sparse_edges() {
+        const N: usize = 30;
+        let build = |storage| {
+            let mut layer = Layer::new();
+            layer.set_edge_storage(storage);
+            for id in 0..N {
+                layer.push_node(Node::new(id, Vec::new(), None)).unwrap();
+            }
+            // fully connected, then thinned out
+            for src in 0..N {
+                match src % 2 {
+                    0 => (0..N).for_each(|dst| layer.add_edge(src, dst, "connect").unwrap()),
+                    _ => layer
+                        .add_edges(src, &(0..N).collect::<Vec<_>>(), "connect")
+                        .unwrap(),
+                }
+            }
+            for src in 0..N {
+                layer.del_edge(src, (src * 7) % N).unwrap();
+            }
+            layer.add_weighted_edge(3, 21, "shortcut", 0.5).unwrap();
+            layer
+        };
+        let per_node = build(EdgeStorage::PerNode);
+        let sparse = build(EdgeStorage::Sparse);
+        assert_eq!(sparse.edge_storage(), EdgeStorage::Sparse);
+        assert!(sparse.nodes.iter().all(|n| n.edges.is_empty()));
+
+        let sorted = |edges: Vec<Cow<Edge>>| {
+            let mut edges = edges
+                .into_iter()
+                .map(|e| (e.src, e.dst, e.desc.clone(), e.seq))
+                .collect::<Vec<_>>();
+            edges.sort_by_key(|e| e.3);
+            edges
+        };
+        for id in 0..N {
+            assert_eq!(
+                sorted(per_node.edges_from(id)),
+                sorted(sparse.edges_from(id))
+            );
+            assert_eq!(sorted(per_node.edges_to(id)), sorted(sparse.edges_to(id)));
+        }
+        assert_eq!(sparse.edges_from(0).len(), N - 1);
+        assert_eq!(
+            sorted(per_node.edges_in_insertion_order()),
+            sorted(sparse.edges_in_insertion_order())
+        );
+        assert_eq!(per_node.adjacency_matrix(), sparse.adjacency_matrix());
+        assert_eq!(per_node.degree_distribution(), sparse.degree_distribution());
+        assert_eq!(
+            per_node.shortest_path(0, 7).unwrap().len(),
+            sparse.shortest_path(0, 7).unwrap().len()
+        );
+
+        // deleting fails the same way in both
+        for mut layer in [per_node.clone(), sparse.clone()] {
+            assert!(matches!(
+                layer.del_edge(0, 0),
+                Err(AtlasError::EdgeNotFound)
+            ));
+            assert!(matches!(
+                layer.del_edge(N, 0),
+                Err(AtlasError::NodeNotFound)
+            ));
+        }
+
+        // sparse layers do not hold parallel edges, nor do they store the endpoints of an edge
+        let mut parallel = sparse.clone();
+        assert!(matches!(
+            parallel.add_edge(3, 21, "connect"),
+            Err(AtlasError::ParallelEdge(3, 21))
+        ));
+        assert!(matches!(
+            parallel.add_edges(1, &[7, 7], "connect"),
+            Err(AtlasError::ParallelEdge(1, 7))
+        ));
+        assert_eq!(parallel, sparse);
+        assert!(std::mem::size_of::<EdgeMeta>() < std::mem::size_of::<Edge>());
+
+        // deleting a node drops its edges
+        let mut pruned = sparse.clone();
+        pruned.del_node(1).unwrap();
+        assert!(pruned.edges_to(1).is_empty());
+        assert_eq!(pruned.edges_from(2).len(), N - 2);
+
+        // switching the storage back and forth keeps the edges
+        let mut switched = sparse.clone();
+        switched.set_edge_storage(EdgeStorage::PerNode);
+        assert_eq!(
+            sorted(switched.edges_from(3)),
+            sorted(per_node.edges_from(3))
+        );
+        switched.set_edge_storage(EdgeStorage::Sparse);
+        assert_eq!(switched, sparse);
+
+        let json = serde_json::to_string(&sparse).unwrap();
+        assert_eq!(serde_json::from_str::<Layer>(&json).unwrap(), sparse);
+    }
+
+    #[test]
+    fn fov_point_cloud() {
+        let mut layer = Layer::new();
+        // coordinates outside the frustum, but the point cloud centroid is inside
+        let mut node = Node::new(0, Vec::new(), Some(Coordinate::new(6.0, 6.0, 6.0)));
+        node.points = vec![
+            Point::new(Coordinate::new(-0.5, 0.0, 1.5), [1.0, 0.0, 0.0]),
+            Point::new(Coordinate::new(0.5, 0.0, 1.5), [1.0, 0.0, 0.0]),
+            Point::new(Coordinate::new(0.0, 0.3, 2.0), [1.0, 0.0, 0.0]),
+        ];
+        layer.push_node(node).unwrap();
+        // coordinates inside the frustum, but the point cloud is behind the observer
+        let mut node = Node::new(1, Vec::new(), Some(Coordinate::new(0.0, 0.0, 1.0)));
+        node.points = vec![Point::new(Coordinate::new(0.0, 0.0, -2.0), [0.0; 3])];
+        layer.push_node(node).unwrap();
+        // point cloud only
+        let mut node = Node::new(2, Vec::new(), None);
+        node.points = vec![Point::new(Coordinate::new(0.0, 0.0, 3.0), [0.0; 3])];
+        layer.push_node(node).unwrap();
+
+        let centroid = layer.nodes[0].representative_point().unwrap();
+        assert!((centroid - Coordinate::new(0.0, 0.1, 5.0 / 3.0)).length() < 1e-5);
+        assert_eq!(
+            layer.nodes[1].representative_point(),
+            Some(Coordinate::new(0.0, 0.0, -2.0))
+        );
+
+        let observed = layer.observable_nodes(cone());
+        let ids = observed.nodes.iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(ids, [0, 2]);
+    }
+
+    #[test]
+    fn fov_iter() {
+        let pts = [
+            Some(Coordinate::new(6.0, 6.0, 6.0)), // outside
+            Some(Coordinate::new(0.0, 0.0, 1.0)), // inside
+            None,
+            Some(Coordinate::new(0.1, 0.0, 2.0)), // inside
+            Some(Coordinate::new(0.0, 0.0, 0.1)), // closer than near
+        ];
+        let mut layer = Layer::new();
+        for (i, p) in pts.iter().enumerate() {
+            layer.push_node(Node::new(i, Vec::new(), *p)).unwrap();
+        }
+        layer.add_edge(1, 0, "connect").unwrap();
+
+        let cone = cone();
+        let ids = layer
+            .iter_observable(cone)
+            .map(|n| n.id)
+            .collect::<Vec<_>>();
+        let observed = layer.observable_nodes(cone);
+        assert_eq!(ids, [1, 3]);
+        assert_eq!(ids, observed.nodes.iter().map(|n| n.id).collect::<Vec<_>>());
+        // edges are left untouched
+        let node = layer.iter_observable(cone).next().unwrap();
+        assert_eq!(node.edges.len(), 1);
+    }
 }
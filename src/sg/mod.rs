@@ -1,19 +1,23 @@
 mod fov;
+mod index;
 mod layer;
 mod node;
+#[allow(clippy::module_inception)]
 mod sg;
 
-pub use fov::Observer;
-pub use layer::Layer;
-pub use node::{Coordinate, Edge, Feature, Node};
+pub use fov::{CoordinateConvention, Observer};
+pub use layer::{EdgeStorage, Layer, LayerKind, MergeReport, NodeMergeDiff, ObservableSet};
+pub use node::{Coordinate, Edge, Feature, MergePolicy, Node, NodeKind, Point};
 pub use sg::SceneGraph;
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashSet;
+    use std::borrow::Cow;
+    use std::collections::{HashMap, HashSet};
+    use std::time::{Duration, Instant};
 
     use super::*;
-    use crate::error::Result;
+    use crate::error::{AtlasError, Result};
 
     #[test]
     fn api() -> Result<()> {
@@ -29,11 +33,11 @@ mod test {
 
         // create a semantic layer and add nodes to layers
         let semantic_layer = sg.new_layer();
-        semantic_layer.push_node(node2);
-        semantic_layer.push_node(node3);
+        semantic_layer.push_node(node2)?;
+        semantic_layer.push_node(node3)?;
         // create the second semantic layer
         let semantic_layer = sg.new_layer();
-        semantic_layer.push_node(node1);
+        semantic_layer.push_node(node1)?;
 
         // nesting
         assert!(sg.nest(id2).under(id1).is_ok());
@@ -74,8 +78,9 @@ mod test {
         Ok(())
     }
 
-    #[test]
-    fn query() -> Result<()> {
+    /// A single-layer scene of furniture with a few relations between them.
+    /// Returns the scene graph along with the ids of the chair, table, wall and clock.
+    fn furniture() -> Result<(SceneGraph, [usize; 4])> {
         let mut sg = SceneGraph::default();
 
         // create nodes
@@ -105,16 +110,23 @@ mod test {
 
         // create layers and add nodes to layers
         let l = sg.new_layer();
-        l.push_node(table);
-        l.push_node(wall);
-        l.push_node(chair);
-        l.push_node(clock);
+        l.push_node(table)?;
+        l.push_node(wall)?;
+        l.push_node(chair)?;
+        l.push_node(clock)?;
 
         l.add_edge(clock_id, wall_id, "supported by")?;
         l.add_edge(table_id, chair_id, "next to")?;
         l.add_edge(chair_id, table_id, "next to")?;
         l.add_edge(table_id, wall_id, "in front of")?;
 
+        Ok((sg, [chair_id, table_id, wall_id, clock_id]))
+    }
+
+    #[test]
+    fn query() -> Result<()> {
+        let (sg, [chair_id, table_id, wall_id, clock_id]) = furniture()?;
+
         // query nodes by label
         let furniture = sg.nodes_having(&["type"]);
         assert_eq!(furniture.len(), 1); // only one layer in the scene graph
@@ -164,6 +176,625 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn edges_from_matching() -> Result<()> {
+        let (sg, [chair_id, table_id, _, _]) = furniture()?;
+
+        let chair_next_to = sg.edges_from_matching(chair_id, "next to");
+        assert_eq!(chair_next_to.len(), 1);
+        assert_eq!(chair_next_to[0].dst, table_id);
+        let table_next_to = sg.edges_from_matching(table_id, "next to");
+        assert_eq!(table_next_to.len(), 1); // the wall is in front of the table, not next to it
+        assert_eq!(table_next_to[0].dst, chair_id);
+        assert!(sg.edges_from_matching(chair_id, "in front of").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn nodes_missing() -> Result<()> {
+        let (sg, [_, _, _, clock_id]) = furniture()?;
+
+        let missing = sg.nodes_missing("affordance");
+        assert_eq!(missing.len(), 1); // only one layer in the scene graph
+        assert_eq!(missing[0].len(), 1); // only the clock has no affordance
+        assert_eq!(missing[0][0].id, clock_id);
+        assert!(sg.nodes_missing("name")[0].is_empty());
+        assert_eq!(sg.nodes_missing("color")[0].len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn symmetry() -> Result<()> {
+        let (sg, [_, table_id, wall_id, clock_id]) = furniture()?;
+        let layer = sg.layer(0)?;
+
+        assert!(layer.is_symmetric("next to").is_empty());
+        assert_eq!(layer.is_symmetric("in front of"), vec![(table_id, wall_id)]);
+        assert_eq!(
+            layer.is_symmetric("supported by"),
+            vec![(clock_id, wall_id)]
+        );
+        assert!(layer.is_symmetric("unknown").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn feature_count() -> Result<()> {
+        let (sg, [chair_id, _, _, clock_id]) = furniture()?;
+
+        assert_eq!(sg.node(chair_id)?.feature_count(), 3);
+        assert_eq!(sg.node(clock_id)?.feature_count(), 2);
+        assert!(sg.node(chair_id)?.has_all_features(&["name", "affordance"]));
+        assert!(!sg.node(clock_id)?.has_all_features(&["name", "affordance"]));
+
+        // all but the clock have 3 features
+        let rich_nodes = sg.nodes_with_min_features(3);
+        assert_eq!(rich_nodes.len(), 1); // only one layer in the scene graph
+        assert_eq!(rich_nodes[0].len(), 3);
+        assert!(rich_nodes[0].iter().all(|n| n.id != clock_id));
+        assert_eq!(sg.nodes_with_min_features(2)[0].len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn feature_histogram() -> Result<()> {
+        let (sg, _) = furniture()?;
+        let histogram = sg.feature_histogram();
+
+        let count = |k: &str, v: &str| histogram.get(&(k.to_string(), v.to_string())).copied();
+        assert_eq!(count("type", "furniture"), Some(2));
+        assert_eq!(count("type", "appliance"), Some(1));
+        assert_eq!(count("name", "chair"), Some(1));
+        assert_eq!(count("type", "vehicle"), None);
+        // 4 names, 3 distinct types and 3 affordances
+        assert_eq!(histogram.len(), 10);
+        assert_eq!(histogram.values().sum::<usize>(), 11);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reverse_edge() -> Result<()> {
+        let (mut sg, [chair, _, wall, clock]) = furniture()?;
+
+        sg.layer_mut(0)?.reverse_edge(clock, wall, "supports")?;
+        assert!(sg.edges_from(clock).is_empty());
+        let edges = sg.edges_from_matching(wall, "supports");
+        assert_eq!(edges.len(), 1);
+        assert_eq!((edges[0].src, edges[0].dst), (wall, clock));
+        assert!(
+            sg.edges_matching("supported by")
+                .iter()
+                .all(|l| l.is_empty())
+        );
+
+        assert!(matches!(
+            sg.layer_mut(0)?.reverse_edge(clock, wall, "supports"),
+            Err(AtlasError::EdgeNotFound)
+        ));
+        assert!(matches!(
+            sg.layer_mut(0)?.reverse_edge(chair, 42, "next to"),
+            Err(AtlasError::NodeNotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn is_empty() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        assert!(sg.is_empty());
+        assert!(sg.new_layer().is_empty());
+        assert!(sg.is_empty());
+
+        let (sg, _) = furniture()?;
+        assert!(!sg.is_empty());
+        assert!(!sg.layer(0)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn edge_desc_histogram() -> Result<()> {
+        let (sg, _) = furniture()?;
+        let histogram = sg.edge_desc_histogram();
+
+        assert_eq!(histogram.get("next to"), Some(&2));
+        assert_eq!(histogram.get("in front of"), Some(&1));
+        assert_eq!(histogram.get("supported by"), Some(&1));
+        assert_eq!(histogram.len(), 3);
+        assert!(SceneGraph::default().edge_desc_histogram().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn generation() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let mut last = sg.generation();
+        let mut assert_bumped = |sg: &SceneGraph| {
+            assert!(sg.generation() != last);
+            last = sg.generation();
+        };
+
+        let chair = sg.new_node(vec![Feature::new("name", "chair")]);
+        let table = sg.new_node(vec![Feature::new("name", "table")]);
+        let room = sg.new_node(vec![Feature::new("name", "room")]);
+        let (chair_id, table_id, room_id) = (chair.id, table.id, room.id);
+        sg.new_layer();
+        assert_bumped(&sg);
+        sg.layer_mut(0)?.push_node(chair)?;
+        assert_bumped(&sg);
+        sg.layer_mut(0)?.push_node(table)?;
+        sg.new_layer().push_node(room)?;
+        assert_bumped(&sg);
+        sg.layer_mut(0)?.add_edge(chair_id, table_id, "next to")?;
+        assert_bumped(&sg);
+        sg.nest(chair_id).under(room_id)?;
+        assert_bumped(&sg);
+        sg.merge(sg.clone())?;
+        assert_bumped(&sg);
+
+        // pure reads leave the generation untouched
+        let generation = sg.generation();
+        let _ = sg.node(chair_id)?;
+        let _ = sg.nodes_matching(&[&Feature::new("name", "chair")]);
+        let _ = sg.edges_from(chair_id);
+        let _ = sg.subgraph(room_id)?;
+        let _ = sg.visible_subgraph(cone(), room_id)?;
+        let _ = sg.ancestors(chair_id)?;
+        assert_eq!(sg.generation(), generation);
+
+        sg.del_node(table_id)?;
+        assert_bumped(&sg);
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_top_layer() -> Result<()> {
+        let (mut sg, [chair_id, table_id, ..]) = furniture()?;
+
+        // the chair turns out to be a stool, and a lamp shows up
+        let mut update = Layer::new();
+        update.push_node(Node::new(
+            chair_id,
+            vec![Feature::new("name", "stool"), Feature::new("color", "red")],
+            None,
+        ))?;
+        let lamp_id = sg.new_node(Vec::new()).id + 10;
+        update.push_node(Node::new(lamp_id, vec![Feature::new("name", "lamp")], None))?;
+
+        let report = sg.merge_top_layer(update)?;
+        assert_eq!(report.updated, vec![chair_id]);
+        assert_eq!(report.added, vec![lamp_id]);
+        let changed = |k: &str, old: Option<&str>, new: &str| {
+            (k.to_string(), old.map(str::to_string), new.to_string())
+        };
+        assert_eq!(
+            report.diffs,
+            vec![NodeMergeDiff {
+                id: chair_id,
+                changed: vec![
+                    changed("name", Some("chair"), "stool"),
+                    changed("color", None, "red")
+                ],
+            }]
+        );
+
+        let chair = sg.node(chair_id)?;
+        assert_eq!(chair.feature("name")?, "stool");
+        assert_eq!(chair.feature("color")?, "red");
+        assert_eq!(chair.feature("affordance")?, "sit");
+        // edges are kept
+        assert_eq!(sg.edges_from(chair_id)[0].dst, table_id);
+        assert_eq!(sg.node(lamp_id)?.feature("name")?, "lamp");
+        // IDs handed out afterwards do not collide with the added node
+        assert!(sg.new_node(Vec::new()).id > lamp_id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_diffs() -> Result<()> {
+        let (mut sg, [_, table_id, ..]) = furniture()?;
+
+        // re-observing a node with one changed feature value, and one unchanged
+        let table = sg.node(table_id)?;
+        let name = table.feature("name")?.to_string();
+        let mut update = Layer::new();
+        update.push_node(Node::new(
+            table_id,
+            vec![Feature::new("name", &name), Feature::new("type", "desk")],
+            None,
+        ))?;
+        let old_type = sg.node(table_id)?.feature("type")?.to_string();
+
+        let report = sg.merge_top_layer(update)?;
+        assert_eq!(report.diffs.len(), 1);
+        assert_eq!(report.diffs[0].id, table_id);
+        assert_eq!(
+            report.diffs[0].changed,
+            vec![("type".to_string(), Some(old_type), "desk".to_string())]
+        );
+
+        // a feature with an empty value is told apart from a missing one
+        let mut update = Layer::new();
+        update.push_node(Node::new(table_id, vec![Feature::new("note", "")], None))?;
+        let report = sg.merge_top_layer(update)?;
+        assert_eq!(
+            report.diffs[0].changed,
+            vec![("note".to_string(), None, String::new())]
+        );
+        let mut update = Layer::new();
+        update.push_node(Node::new(
+            table_id,
+            vec![Feature::new("note", "wobbly")],
+            None,
+        ))?;
+        let report = sg.merge_top_layer(update)?;
+        assert_eq!(
+            report.diffs[0].changed,
+            vec![(
+                "note".to_string(),
+                Some(String::new()),
+                "wobbly".to_string()
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_replace_edges() -> Result<()> {
+        let (mut sg, [chair_id, table_id, wall_id, _]) = furniture()?;
+
+        // re-observation of the table: it is no longer in front of the wall
+        let mut update = sg.clone();
+        update.layer_mut(0)?.del_edge(table_id, wall_id)?;
+        assert_eq!(update.edges_from(table_id).len(), 1);
+
+        // union semantics keep the stale edge around
+        let mut union = sg.clone();
+        union.merge(update.clone())?;
+        assert_eq!(union.edges_from(table_id).len(), 2);
+
+        // replace semantics drop it
+        sg.merge_with(update, MergePolicy::ReplaceEdges)?;
+        let edges = sg.edges_from(table_id);
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].dst, chair_id);
+        assert_eq!(edges[0].desc, "next to");
+
+        Ok(())
+    }
+
+    #[test]
+    fn subgraph_merge_back() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let room = sg.new_node(vec![Feature::new("name", "room")]);
+        let hall = sg.new_node(vec![Feature::new("name", "hall")]);
+        let chair = sg.new_node(vec![Feature::new("name", "chair")]);
+        let door = sg.new_node(vec![Feature::new("name", "door")]);
+        let (room_id, hall_id, chair_id, door_id) = (room.id, hall.id, chair.id, door.id);
+        let layer = sg.new_layer();
+        layer.push_node(chair)?;
+        layer.push_node(door)?;
+        let layer = sg.new_layer();
+        layer.push_node(room)?;
+        layer.push_node(hall)?;
+        sg.nest(chair_id).under(room_id)?;
+        sg.nest(door_id).under(hall_id)?;
+
+        // derive a subgraph, which only holds the room and its chair
+        let mut sub = sg.subgraph(room_id)?;
+        assert_eq!(sub.layer(0)?.nodes.len(), 1);
+        assert_eq!(sub.layer(1)?.nodes.len(), 1);
+
+        // add a node to the subgraph; its ID must not collide with nodes left out of it
+        let table = sub.new_node(vec![Feature::new("name", "table")]);
+        let table_id = table.id;
+        assert!(![room_id, hall_id, chair_id, door_id].contains(&table_id));
+        sub.layer_mut(0)?.push_node(table)?;
+        sub.nest(table_id).under(room_id)?;
+
+        sg.merge(sub)?;
+        assert_eq!(sg.node(door_id)?.feature("name")?, "door");
+        assert_eq!(sg.node(table_id)?.feature("name")?, "table");
+        assert_eq!(sg.node(table_id)?.pid, Some(room_id));
+        assert_eq!(sg.node(room_id)?.children, vec![chair_id, table_id]);
+        assert_eq!(sg.node(door_id)?.pid, Some(hall_id));
+
+        // new nodes of the parent continue after the merged ones
+        let lamp = sg.new_node(Vec::new());
+        let lamp_id = lamp.id;
+        assert!(![room_id, hall_id, chair_id, door_id, table_id].contains(&lamp_id));
+        sg.layer_mut(1)?.push_node(lamp)?;
+
+        // the subgraph of a childless node keeps the original layer indices
+        let sub = sg.subgraph(lamp_id)?;
+        assert!(sub.layer(0)?.nodes.is_empty());
+        assert!(sub.layer(1)?.node(lamp_id).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reparent() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let point = sg.new_coordinates(0.0, 0.0, 1.0, Vec::new());
+        let chair = sg.new_node(vec![Feature::new("name", "chair")]);
+        let table = sg.new_node(vec![Feature::new("name", "table")]);
+        let room = sg.new_node(vec![Feature::new("name", "room")]);
+        let (point_id, chair_id, table_id, room_id) = (point.id, chair.id, table.id, room.id);
+        sg.new_layer().push_node(point)?;
+        let layer = sg.new_layer();
+        layer.push_node(chair)?;
+        layer.push_node(table)?;
+        sg.new_layer().push_node(room)?;
+        sg.nest(chair_id).under(room_id)?;
+        sg.nest(table_id).under(room_id)?;
+
+        // attach, then move the point between the two semantic parents
+        sg.reparent(point_id, chair_id)?;
+        assert_eq!(sg.node(point_id)?.pid, Some(chair_id));
+        assert_eq!(sg.node(chair_id)?.children, vec![point_id]);
+        sg.reparent(point_id, table_id)?;
+        assert_eq!(sg.node(point_id)?.pid, Some(table_id));
+        assert!(sg.node(chair_id)?.children.is_empty());
+        assert_eq!(sg.node(table_id)?.children, vec![point_id]);
+        assert_eq!(sg.ancestors(point_id)?, vec![table_id, room_id]);
+
+        // parents must be exactly one layer above
+        assert!(matches!(
+            sg.reparent(point_id, room_id),
+            Err(AtlasError::InvalidLayersForNesting(0, 2))
+        ));
+        assert!(sg.reparent(chair_id, point_id).is_err());
+        assert_eq!(sg.node(point_id)?.pid, Some(table_id));
+
+        // a corrupted hierarchy with a loop is reported rather than followed forever
+        sg.node_mut(room_id)?.pid = Some(table_id);
+        assert!(matches!(
+            sg.reparent(point_id, chair_id),
+            Err(AtlasError::CyclicNesting(_, _))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn boundary_layers() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        let semantic_id = NUM_COOR_NODES;
+        let top = sg.layer_count() - 1;
+
+        assert_eq!(sg.layer_above(0)?, 1);
+        assert!(matches!(
+            sg.layer_above(top),
+            Err(AtlasError::LayerOutOfBounds(3, 3))
+        ));
+        assert!(sg.layer_above(usize::MAX).is_err());
+        assert_eq!(sg.layer_below(top)?, top - 1);
+        assert!(matches!(
+            sg.layer_below(0),
+            Err(AtlasError::NoLayerBelow(0))
+        ));
+
+        // nesting under a node of the bottom layer
+        assert!(matches!(
+            sg.nest(semantic_id).under(0),
+            Err(AtlasError::InvalidLayersForNesting(1, 0))
+        ));
+        assert!(sg.nest(0).under(1).is_err());
+
+        // subgraphs rooted at the bottom and the top layers
+        let sub = sg.subgraph(0)?;
+        assert_eq!(sub.layer_count(), 1);
+        assert!(sub.layer(0)?.node(0).is_ok());
+        let sub = sg.subgraph(root_id)?;
+        assert_eq!(sub.layer_count(), 3);
+        assert_eq!(sub.layer(0)?.nodes.len(), NUM_COOR_NODES);
+
+        // deleting a top layer node whose parent link points past the top layer
+        let orphan = sg.new_node(Vec::new());
+        let orphan_id = orphan.id;
+        sg.layer_mut(top)?.push_node(orphan)?;
+        sg.node_mut(orphan_id)?.pid = Some(root_id);
+        assert!(matches!(
+            sg.del_node(orphan_id),
+            Err(AtlasError::LayerOutOfBounds(3, 3))
+        ));
+
+        // deleting a bottom layer node that claims to have children
+        sg.node_mut(0)?.children.push(root_id);
+        assert!(matches!(sg.del_node(0), Err(AtlasError::NoLayerBelow(0))));
+
+        // deleting through all layers from the top still works
+        sg.del_node(root_id)?;
+        assert!(sg.layer(0)?.nodes.len() < NUM_COOR_NODES);
+
+        Ok(())
+    }
+
+    #[test]
+    fn take_layer_nodes() -> Result<()> {
+        let (mut sg, ids) = furniture()?;
+
+        let nodes = sg.take_layer_nodes(0)?;
+        assert!(sg.layer(0)?.nodes.is_empty());
+        assert_eq!(nodes.len(), 4);
+        for id in ids {
+            assert!(nodes.iter().any(|n| n.id == id));
+            assert!(sg.node(id).is_err());
+        }
+        // edges travel along with their nodes
+        assert_eq!(nodes.iter().map(|n| n.edges.len()).sum::<usize>(), 4);
+        assert!(sg.take_layer_nodes(1).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_round_trip() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
+        let bytes = sg.to_bytes()?;
+        let decoded = SceneGraph::from_bytes(&bytes)?;
+        assert_eq!(decoded, sg);
+        assert_eq!(decoded.node(0)?.pid, sg.node(0)?.pid);
+        assert_eq!(decoded.node(root_id)?.children, sg.node(root_id)?.children);
+
+        let json = serde_json::to_vec(&sg).unwrap();
+        assert!(bytes.len() < json.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn json_round_trip() -> Result<()> {
+        let (mut sg, [chair_id, table_id, ..]) = furniture()?;
+        let room = sg.new_node(vec![Feature::new("name", "dining room")]);
+        let room_id = room.id;
+        sg.new_layer().push_node(room)?;
+        sg.nest(chair_id).under(room_id)?;
+        sg.nest(table_id).under(room_id)?;
+
+        let mut decoded = SceneGraph::from_json(&sg.to_json()?)?;
+        assert_eq!(decoded, sg);
+        assert_eq!(decoded.node(room_id)?.children, [chair_id, table_id]);
+        assert_eq!(decoded.node(chair_id)?.pid, Some(room_id));
+        assert_eq!(decoded.edges_from(table_id), sg.edges_from(table_id));
+        assert_eq!(decoded.new_node(Vec::new()).id, sg.new_node(Vec::new()).id);
+
+        assert!(matches!(
+            SceneGraph::<()>::from_json("{"),
+            Err(AtlasError::Json(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_json() -> Result<()> {
+        let (sg, _) = fov_scene()?;
+
+        let mut buf = Vec::new();
+        sg.write_json(&mut buf)?;
+        let decoded: SceneGraph = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(decoded, sg);
+
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_visible() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
+        let mut buf = Vec::new();
+        sg.serialize_visible(cone(), root_id, &mut buf)?;
+        let decoded: SceneGraph = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(decoded, sg.visible_subgraph(cone(), root_id)?);
+
+        assert!(
+            sg.serialize_visible(cone(), usize::MAX, Vec::new())
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_load() -> Result<()> {
+        let (sg, [chair_id, ..]) = furniture()?;
+        let dir = std::env::temp_dir();
+
+        let path = dir.join(format!("atlas-save-load-{}.json", std::process::id()));
+        sg.save(&path)?;
+        let loaded = SceneGraph::load(&path);
+        std::fs::remove_file(&path)?;
+        let loaded = loaded?;
+        assert_eq!(loaded, sg);
+        assert_eq!(loaded.node(chair_id)?.feature("name")?, "chair");
+
+        #[cfg(feature = "bincode")]
+        {
+            let path = dir.join(format!("atlas-save-load-{}.bin", std::process::id()));
+            sg.save(&path)?;
+            let loaded = SceneGraph::load(&path);
+            std::fs::remove_file(&path)?;
+            assert_eq!(loaded?, sg);
+        }
+
+        assert!(matches!(
+            sg.save(dir.join("atlas-save-load.txt")),
+            Err(AtlasError::UnsupportedFormat(ext)) if ext == "txt"
+        ));
+        assert!(matches!(
+            SceneGraph::<()>::load(dir.join("atlas-does-not-exist.json")),
+            Err(AtlasError::Io(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ids_survive_reload() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        sg.del_node(NUM_COOR_NODES + 3)?;
+        sg.del_node(7)?;
+
+        let json = serde_json::to_string(&sg).unwrap();
+        let mut loaded: SceneGraph = serde_json::from_str(&json).unwrap();
+
+        for (_, node) in sg.bfs(root_id) {
+            let reloaded = loaded.node(node.id)?;
+            assert_eq!(reloaded.pid, node.pid);
+            assert_eq!(reloaded.children, node.children);
+            assert_eq!(reloaded.edges, node.edges);
+        }
+        assert!(loaded.node(7).is_err());
+
+        let fresh = sg.new_node(Vec::new()).id;
+        assert_eq!(loaded.new_node(Vec::new()).id, fresh);
+        assert_eq!(fresh, NUM_COOR_NODES + NUM_SEMANTIC_NODES + 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn payload() -> Result<()> {
+        #[derive(Debug, Clone, PartialEq)]
+        struct Mesh {
+            mesh_id: u32,
+            uri: String,
+        }
+
+        let mut sg = SceneGraph::<Mesh>::new();
+        let chair = sg.new_node_with(
+            vec![Feature::new("name", "chair")],
+            Mesh {
+                mesh_id: 7,
+                uri: "meshes/chair.glb".to_string(),
+            },
+        );
+        let chair_id = chair.id;
+        sg.new_layer().push_node(chair)?;
+
+        assert_eq!(sg.node_data(chair_id)?.mesh_id, 7);
+        assert_eq!(sg.node_data(chair_id)?.uri, "meshes/chair.glb");
+        sg.node_data_mut(chair_id)?.mesh_id = 8;
+        assert_eq!(sg.node(chair_id)?.data.mesh_id, 8);
+        assert!(sg.node_data(chair_id + 1).is_err());
+
+        Ok(())
+    }
+
     fn cone() -> Observer {
         // Observer at origin, yaw=30°, pitch=5°, roll=0°
         let pos = Coordinate::new(0.0, 0.0, 0.0);
@@ -179,15 +810,19 @@ mod test {
         Observer::from_ypr(pos, yaw, pitch, roll, half_angle, near, far)
     }
 
-    #[test]
-    fn fov() -> Result<()> {
+    const NUM_COOR_NODES: usize = 150;
+    const NUM_SEMANTIC_NODES: usize = NUM_COOR_NODES / 10;
+
+    /// A three-layer scene: coordinate nodes, half of them inside the FOV of [`cone`], nested
+    /// under semantic nodes which are in turn nested under a single root node.
+    /// Returns the scene graph along with the id of the root node.
+    fn fov_scene() -> Result<(SceneGraph, usize)> {
         let mut sg = SceneGraph::default();
         let inside_coords = Coordinate::new(0.0, 0.0, 1.0);
         let outside_coords = Coordinate::new(6.0, 6.0, 6.0);
 
         // first layer:
         // 100 coordinate nodes, half inside FOV, half outside, fully connected
-        const NUM_COOR_NODES: usize = 150;
         let mut nodes = Vec::new();
         for id in 0..NUM_COOR_NODES {
             let coords = if (id / 15) % 2 == 0 {
@@ -200,12 +835,12 @@ mod test {
         }
         let layer = sg.new_layer();
         for node in nodes {
-            layer.push_node(node);
+            layer.push_node(node)?;
         }
 
         for src in 0..NUM_COOR_NODES {
             for dst in 0..NUM_COOR_NODES {
-                assert!(layer.add_edge(src, dst, "connect").is_ok());
+                layer.add_edge(src, dst, "connect")?;
             }
         }
 
@@ -213,27 +848,22 @@ mod test {
         // 10 semantic nodes, each parenting 10 coordinate nodes from the first layers
         // fully connected. around 5 semantic nodes father no visible coordinate nodes
         let mut nodes = Vec::new();
-        const NUM_SEMANTIC_NODES: usize = NUM_COOR_NODES / 10;
         for id in 0..NUM_SEMANTIC_NODES {
             let node = sg.new_node(vec![Feature::new("name", &format!("semantic {}", id))]);
             nodes.push(node);
         }
         let layer = sg.new_layer();
         for node in nodes {
-            layer.push_node(node);
+            layer.push_node(node)?;
         }
         for src in 0..NUM_SEMANTIC_NODES {
             for dst in 0..NUM_SEMANTIC_NODES {
-                assert!(
-                    layer
-                        .add_edge(NUM_COOR_NODES + src, NUM_COOR_NODES + dst, "connect")
-                        .is_ok()
-                );
+                layer.add_edge(NUM_COOR_NODES + src, NUM_COOR_NODES + dst, "connect")?;
             }
         }
         // eatch 10 nodes from the first layer under each semantic node
         for id in 0..NUM_COOR_NODES {
-            assert!(sg.nest(id).under(NUM_COOR_NODES + id / 10).is_ok());
+            sg.nest(id).under(NUM_COOR_NODES + id / 10)?;
         }
 
         // Third layer:
@@ -241,11 +871,18 @@ mod test {
         let root_node = sg.new_node(vec![Feature::new("name", "root")]);
         let root_id = root_node.id;
         let layer = sg.new_layer();
-        layer.push_node(root_node);
+        layer.push_node(root_node)?;
         for id in 0..NUM_SEMANTIC_NODES {
-            assert!(sg.nest(NUM_COOR_NODES + id).under(root_id).is_ok());
+            sg.nest(NUM_COOR_NODES + id).under(root_id)?;
         }
 
+        Ok((sg, root_id))
+    }
+
+    #[test]
+    fn fov() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
         // Query visible subgraph under root
         let cone = cone();
         let observed_sg = sg.visible_subgraph(cone, root_id)?;
@@ -298,4 +935,1931 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn coverage_equivalent() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
+        // narrower and shorter, but still covering the inside coordinates only
+        let narrow = Observer::from_ypr(
+            Coordinate::ZERO,
+            0.0,
+            0.0,
+            0.0,
+            10_f32.to_radians(),
+            0.5,
+            2.0,
+        );
+        // looking away from every node
+        let away = Observer::from_ypr(
+            Coordinate::ZERO,
+            180_f32.to_radians(),
+            0.0,
+            0.0,
+            35_f32.to_radians(),
+            0.6,
+            6.0,
+        );
+        assert!(sg.coverage_equivalent(&cone(), &narrow, root_id)?);
+        assert!(!sg.coverage_equivalent(&cone(), &away, root_id)?);
+        assert!(!sg.coverage_equivalent(&narrow, &Observer::everything(), root_id)?);
+        assert!(
+            sg.coverage_equivalent(&cone(), &narrow, NUM_COOR_NODES + 100)
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn recompute_coordinates_from_children() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        let semantic_id = NUM_COOR_NODES;
+        let unpositioned = sg.new_node(Vec::new());
+        let unpositioned_id = unpositioned.id;
+        sg.layer_mut(1)?.push_node(unpositioned)?;
+
+        sg.recompute_coordinates_from_children(1)?;
+        // all ten children of the first semantic node are at the same inside coordinates
+        assert_eq!(
+            sg.node(semantic_id)?.coordinates,
+            Some(Coordinate::new(0.0, 0.0, 1.0))
+        );
+        // the second one has five children inside and five outside
+        let centroid = sg.node(semantic_id + 1)?.coordinates.unwrap();
+        assert!((centroid - Coordinate::new(3.0, 3.0, 3.5)).length() < 1e-5);
+        // no children, no coordinates
+        assert_eq!(sg.node(unpositioned_id)?.coordinates, None);
+
+        // the root is positioned from the freshly computed semantic coordinates
+        assert_eq!(sg.node(root_id)?.coordinates, None);
+        sg.recompute_coordinates_from_children(2)?;
+        assert!(sg.node(root_id)?.coordinates.is_some());
+
+        assert!(sg.recompute_coordinates_from_children(0).is_err());
+        assert!(sg.recompute_coordinates_from_children(3).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn dfs() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
+        let order = sg.dfs(root_id).map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(order.len(), 1 + NUM_SEMANTIC_NODES + NUM_COOR_NODES);
+        // root, then each semantic node directly followed by its ten coordinate nodes
+        let mut expected = vec![root_id];
+        for sid in 0..NUM_SEMANTIC_NODES {
+            expected.push(NUM_COOR_NODES + sid);
+            expected.extend(sid * 10..(sid + 1) * 10);
+        }
+        assert_eq!(order, expected);
+
+        // early termination and sub-trees
+        let first = sg.dfs(root_id).take(3).map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(first, vec![root_id, NUM_COOR_NODES, 0]);
+        let sub = sg.dfs(NUM_COOR_NODES + 1).map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(
+            sub,
+            [NUM_COOR_NODES + 1]
+                .into_iter()
+                .chain(10..20)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(sg.dfs(0).count(), 1);
+        assert_eq!(sg.dfs(root_id + 1).count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bfs() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
+        let visited = sg.bfs(root_id).collect::<Vec<_>>();
+        assert_eq!(visited.len(), 1 + NUM_SEMANTIC_NODES + NUM_COOR_NODES);
+        // depths never decrease and each depth lives on a single layer below the root
+        assert!(visited.windows(2).all(|w| w[0].0 <= w[1].0));
+        for (depth, node) in &visited {
+            assert_eq!(sg.layer_of(node.id)?, 2 - depth);
+        }
+
+        let levels = visited.iter().map(|(d, n)| (*d, n.id)).collect::<Vec<_>>();
+        assert_eq!(levels[0], (0, root_id));
+        assert_eq!(
+            levels[1..=NUM_SEMANTIC_NODES],
+            (NUM_COOR_NODES..NUM_COOR_NODES + NUM_SEMANTIC_NODES)
+                .map(|id| (1, id))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            levels[NUM_SEMANTIC_NODES + 1..],
+            (0..NUM_COOR_NODES).map(|id| (2, id)).collect::<Vec<_>>()
+        );
+
+        // level-limited processing
+        assert_eq!(sg.bfs(root_id).take_while(|(d, _)| *d < 2).count(), 16);
+        assert_eq!(sg.bfs(root_id + 1).count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn count_visible() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+        let observer = cone();
+
+        let visible = sg.visible_subgraph(observer, root_id)?;
+        assert_eq!(
+            sg.count_visible(observer, root_id)?,
+            visible.layer(0)?.nodes.len()
+        );
+        assert_eq!(sg.count_visible(observer, root_id)?, 75);
+        assert_eq!(sg.count_visible(observer, NUM_COOR_NODES)?, 10);
+        assert_eq!(sg.count_visible(observer, NUM_COOR_NODES + 1)?, 5);
+        assert!(matches!(
+            sg.count_visible(observer, root_id + 1),
+            Err(AtlasError::NodeNotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn freeze_layer() -> Result<()> {
+        let (mut sg, [chair, table, wall, clock]) = furniture()?;
+        let (mut fov, root_id) = fov_scene()?;
+
+        sg.freeze_layer(0)?;
+        assert!(sg.layer(0)?.is_frozen());
+        assert!(matches!(
+            sg.layer_mut(0)?.add_edge(chair, wall, "next to"),
+            Err(AtlasError::LayerFrozen)
+        ));
+        assert!(matches!(
+            sg.layer_mut(0)?.del_edge(table, chair),
+            Err(AtlasError::LayerFrozen)
+        ));
+        let lamp = sg.new_node(vec![Feature::new("name", "lamp")]);
+        assert!(matches!(
+            sg.layer_mut(0)?.push_node(lamp),
+            Err(AtlasError::LayerFrozen)
+        ));
+        assert!(matches!(sg.del_node(clock), Err(AtlasError::LayerFrozen)));
+        assert_eq!(sg.layer(0)?.nodes.len(), 4);
+        assert!(sg.edges_from(chair).iter().all(|e| e.dst != wall));
+
+        sg.unfreeze_layer(0)?;
+        assert!(!sg.layer(0)?.is_frozen());
+        sg.layer_mut(0)?.add_edge(chair, wall, "next to")?;
+        assert!(sg.edges_from(chair).iter().any(|e| e.dst == wall));
+        assert!(matches!(
+            sg.freeze_layer(1),
+            Err(AtlasError::LayerOutOfBounds(1, 1))
+        ));
+
+        // deleting a node fails without side effects if any of its descendants is frozen
+        fov.freeze_layer(0)?;
+        assert!(matches!(
+            fov.del_node(NUM_COOR_NODES),
+            Err(AtlasError::LayerFrozen)
+        ));
+        assert!(fov.node(root_id)?.children().contains(&NUM_COOR_NODES));
+        assert_eq!(
+            fov.dfs(root_id).count(),
+            1 + NUM_SEMANTIC_NODES + NUM_COOR_NODES
+        );
+
+        // so does merging into a graph with any frozen layer to merge into
+        fov.unfreeze_layer(0)?;
+        fov.freeze_layer(2)?;
+        let mut update = fov.clone();
+        update.spawn_node(0, vec![Feature::new("name", "speck")])?;
+        update.add_cross_edge(0, root_id, "part of")?;
+        let (before, generation) = (fov.clone(), fov.generation());
+        assert!(matches!(fov.merge(update), Err(AtlasError::LayerFrozen)));
+        assert_eq!(fov, before);
+        assert_eq!(fov.generation(), generation);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cross_edges() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        let semantic = NUM_COOR_NODES + 5;
+        assert_ne!(sg.node(0)?.pid(), Some(semantic));
+
+        sg.add_cross_edge(0, semantic, "instance of")?;
+        sg.add_cross_edge(semantic, root_id, "part of")?;
+        let edges = sg.cross_edges_from(0);
+        assert_eq!(edges.len(), 1);
+        assert_eq!(*edges[0], (0, semantic, "instance of".to_string()));
+        assert!(sg.cross_edges_from(1).is_empty());
+        // intra-layer edges are untouched
+        assert!(sg.edges_from(0).iter().all(|e| e.dst < NUM_COOR_NODES));
+
+        assert!(matches!(
+            sg.add_cross_edge(0, root_id + 1, "part of"),
+            Err(AtlasError::NodeNotFound)
+        ));
+        assert!(matches!(
+            sg.add_cross_edge(root_id, 0, "contains"),
+            Err(AtlasError::CyclicCrossEdge(src, dst)) if src == root_id && dst == 0
+        ));
+        assert!(matches!(
+            sg.add_cross_edge(0, 0, "self"),
+            Err(AtlasError::CyclicCrossEdge(0, 0))
+        ));
+
+        // deleting a node drops its cross edges
+        sg.del_node(semantic)?;
+        assert!(sg.cross_edges_from(0).is_empty());
+        assert!(sg.cross_edges_from(semantic).is_empty());
+
+        // merged cross edges are validated too, leaving out the ones closing a cycle
+        let mut update = sg.clone();
+        update.add_cross_edge(root_id, 1, "contains")?;
+        update.add_cross_edge(1, 2, "next to")?;
+        sg.add_cross_edge(1, root_id, "part of")?;
+        sg.merge(update)?;
+        assert_eq!(
+            sg.cross_edges_from(1),
+            [
+                &(1, root_id, "part of".to_string()),
+                &(1, 2, "next to".to_string())
+            ]
+        );
+        assert!(sg.cross_edges_from(root_id).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn isolated_nodes() -> Result<()> {
+        let (mut sg, [chair, ..]) = furniture()?;
+        assert!(sg.layer(0)?.isolated_nodes().is_empty());
+
+        let lamp = sg.new_node(vec![Feature::new("name", "lamp")]);
+        let lamp_id = lamp.id;
+        sg.layer_mut(0)?.push_node(lamp)?;
+        assert_eq!(sg.layer(0)?.isolated_nodes(), vec![lamp_id]);
+
+        // a node with only inbound edges is not isolated
+        sg.layer_mut(0)?.add_edge(chair, lamp_id, "next to")?;
+        assert!(sg.layer(0)?.isolated_nodes().is_empty());
+
+        let room = sg.new_node(vec![Feature::new("name", "room")]);
+        let room_id = room.id;
+        sg.new_layer().push_node(room)?;
+        assert_eq!(sg.isolated_nodes_per_layer(), vec![vec![], vec![room_id]]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_point_clouds() -> Result<()> {
+        let point = |x| Point::new(Coordinate::new(x, 0.0, 1.0), [1.0, 0.0, 0.0]);
+        let mut sofa = Node::new(0, vec![Feature::new("name", "sofa")], None);
+        sofa.points = vec![point(0.0), point(1.0)];
+        let mut update = Node::new(0, vec![], None);
+        update.points = vec![point(2.0)];
+
+        sofa.merge(update)?;
+        assert_eq!(sofa.points, vec![point(0.0), point(1.0), point(2.0)]);
+
+        // merging through the scene graph concatenates the clouds as well
+        let mut sg = SceneGraph::default();
+        sg.new_layer().push_node(sofa.clone())?;
+        let mut other = SceneGraph::default();
+        let mut update = Node::new(0, vec![], None);
+        update.points = vec![point(3.0)];
+        other.new_layer().push_node(update)?;
+        sg.merge(other)?;
+        assert_eq!(sg.node(0)?.points.len(), 4);
+        assert_eq!(sg.node(0)?.points[3], point(3.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn quantize_coordinates() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let jittered = [(0.98, 2.03, -0.51), (1.02, 1.97, -0.49), (1.0, 2.0, -0.5)];
+        let layer = sg.new_layer();
+        for (id, (x, y, z)) in jittered.into_iter().enumerate() {
+            layer.push_node(Node::new(id, vec![], Some(Coordinate::new(x, y, z))))?;
+        }
+        layer.push_node(Node::new(jittered.len(), vec![], None))?;
+
+        assert_eq!(sg.quantize_coordinates(0, 0.5)?, 2);
+        for id in 0..jittered.len() {
+            assert_eq!(
+                sg.node(id)?.coordinates,
+                Some(Coordinate::new(1.0, 2.0, -0.5))
+            );
+        }
+        assert_eq!(sg.node(jittered.len())?.coordinates, None);
+        assert_eq!(sg.quantize_coordinates(0, 0.5)?, 0);
+        assert_eq!(sg.quantize_coordinates(0, 0.0)?, 0);
+        for grid in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            assert_eq!(sg.quantize_coordinates(0, grid)?, 0);
+        }
+        assert_eq!(
+            sg.node(0)?.coordinates,
+            Some(Coordinate::new(1.0, 2.0, -0.5))
+        );
+
+        sg.freeze_layer(0)?;
+        assert!(matches!(
+            sg.quantize_coordinates(0, 2.0),
+            Err(AtlasError::LayerFrozen)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn split_node() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let mut sofa = sg.new_node(vec![Feature::new("name", "sofa")]);
+        let sofa_id = sofa.id;
+        sofa.points = [-2.0, -1.0, -0.5, 0.5, 1.0]
+            .into_iter()
+            .map(|x| Point::new(Coordinate::new(x, 0.0, 1.0), [0.5, 0.5, 0.5]))
+            .collect();
+        let room = sg.new_node(vec![Feature::new("name", "room")]);
+        let room_id = room.id;
+        sg.new_layer().push_node(sofa)?;
+        sg.new_layer().push_node(room)?;
+        sg.nest(sofa_id).under(room_id)?;
+
+        let split_id = sg.split_node(sofa_id, Coordinate::ZERO, Coordinate::X)?;
+        let xs = |nid| -> Result<Vec<f32>> {
+            Ok(sg.node(nid)?.points.iter().map(|p| p.position.x).collect())
+        };
+        assert_eq!(xs(sofa_id)?, vec![0.5, 1.0]);
+        assert_eq!(xs(split_id)?, vec![-2.0, -1.0, -0.5]);
+
+        let split = sg.node(split_id)?;
+        assert_eq!(sg.layer_of(split_id)?, 0);
+        assert_eq!(split.pid(), Some(room_id));
+        assert_eq!(split.feature("name")?, "sofa");
+        assert_eq!(sg.node(room_id)?.children(), &[sofa_id, split_id]);
+        // both halves are positioned at their own points
+        assert_eq!(
+            split.coordinates,
+            Some(Coordinate::new(-3.5 / 3.0, 0.0, 1.0))
+        );
+        assert_eq!(
+            sg.node(sofa_id)?.coordinates,
+            Some(Coordinate::new(0.75, 0.0, 1.0))
+        );
+
+        assert!(matches!(
+            sg.split_node(room_id, Coordinate::ZERO, Coordinate::X),
+            Err(AtlasError::PointNotFound)
+        ));
+
+        // a plane not cutting through the point cloud does not split it
+        let before = sg.clone();
+        for normal in [Coordinate::X, -Coordinate::X] {
+            assert!(matches!(
+                sg.split_node(sofa_id, Coordinate::new(5.0, 0.0, 0.0), normal),
+                Err(AtlasError::PointNotFound)
+            ));
+        }
+        assert_eq!(sg, before);
+
+        Ok(())
+    }
+
+    #[test]
+    fn compact_ids() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        for sid in (NUM_COOR_NODES..NUM_COOR_NODES + NUM_SEMANTIC_NODES).step_by(2) {
+            sg.del_node(sid)?;
+        }
+        let remaining = sg.dfs(root_id).count();
+        assert_eq!(remaining, 1 + 7 + 70);
+        sg.add_cross_edge(10, NUM_COOR_NODES + 1, "instance of")?;
+
+        let mapping = sg.compact_ids();
+        assert_eq!(mapping.len(), remaining);
+        let new_root = mapping[&root_id];
+        assert_eq!(new_root, remaining - 1);
+
+        // IDs are dense and contiguous, and the counter is right past them
+        let mut ids = sg.dfs(new_root).map(|n| n.id).collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, (0..remaining).collect::<Vec<_>>());
+        assert_eq!(sg.new_node(vec![]).id, remaining);
+
+        // links are rewritten
+        let semantic = mapping[&(NUM_COOR_NODES + 1)];
+        assert_eq!(mapping[&10], 0);
+        assert_eq!(sg.node(0)?.pid(), Some(semantic));
+        assert_eq!(sg.node(semantic)?.pid(), Some(new_root));
+        assert_eq!(sg.node(semantic)?.children(), (0..10).collect::<Vec<_>>());
+        assert!(sg.edges_from(0).iter().all(|e| e.src == 0 && e.dst < 70));
+        assert_eq!(sg.edges_from(0).len(), 70);
+        assert_eq!(
+            *sg.cross_edges_from(0)[0],
+            (0, semantic, "instance of".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn visible_within_radius() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let near = sg.new_coordinates(0.0, 0.0, 1.0, vec![]);
+        let far = sg.new_coordinates(0.0, 0.0, 5.0, vec![]);
+        let behind = sg.new_coordinates(0.0, 0.0, -1.0, vec![]);
+        let room = sg.new_node(vec![]);
+        let ids @ [near_id, far_id, behind_id, room_id] = [near.id, far.id, behind.id, room.id];
+        let layer = sg.new_layer();
+        layer.push_node(near)?;
+        layer.push_node(far)?;
+        layer.push_node(behind)?;
+        sg.new_layer().push_node(room)?;
+        for nid in &ids[..3] {
+            sg.nest(*nid).under(room_id)?;
+        }
+
+        // the far node is within the cone, but beyond the radius
+        assert!(sg.visible_subgraph(cone(), room_id)?.node(far_id).is_ok());
+        let visible = sg.visible_within_radius(cone(), room_id, 2.0)?;
+        assert!(visible.node(near_id).is_ok());
+        assert!(visible.node(far_id).is_err());
+        assert!(visible.node(behind_id).is_err());
+        assert!(visible.node(room_id).is_ok());
+
+        // upper layers are pruned when nothing is left below
+        let visible = sg.visible_within_radius(cone(), room_id, 0.5)?;
+        assert!(visible.node(room_id).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn nodes_with_numeric_feature() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let heights = ["0.45", "0.75", " 1.2 ", "2", "tall"];
+        let layer = sg.new_layer();
+        for (id, height) in heights.into_iter().enumerate() {
+            layer.push_node(Node::new(id, vec![Feature::new("height", height)], None))?;
+        }
+        let room = sg.new_node(vec![Feature::new("name", "room")]);
+        sg.new_layer().push_node(room)?;
+
+        assert_eq!(Feature::new("height", " 1.2 ").as_f64(), Some(1.2));
+        assert_eq!(Feature::new("height", "tall").as_f64(), None);
+
+        let ids = |min, max| -> Vec<Vec<usize>> {
+            sg.nodes_with_numeric_feature("height", min, max)
+                .into_iter()
+                .map(|l| l.into_iter().map(|n| n.id).collect())
+                .collect()
+        };
+        assert_eq!(ids(0.5, 1.5), vec![vec![1, 2], vec![]]);
+        assert_eq!(ids(0.45, 2.0), vec![vec![0, 1, 2, 3], vec![]]);
+        assert_eq!(ids(3.0, 4.0), vec![Vec::<usize>::new(), vec![]]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn top_layers() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        sg.add_cross_edge(0, root_id, "part of")?;
+        sg.add_cross_edge(NUM_COOR_NODES, root_id, "part of")?;
+
+        let top = sg.top_layers(1);
+        assert_eq!(top.layer_count(), 1);
+        assert!(top.node(root_id)?.children().is_empty());
+        assert!(top.node(root_id)?.pid().is_none());
+        assert!(top.node(NUM_COOR_NODES).is_err());
+        assert!(top.cross_edges_from(0).is_empty());
+        // the original graph is untouched
+        assert_eq!(sg.node(root_id)?.children().len(), NUM_SEMANTIC_NODES);
+
+        let semantic = sg.top_layers(2);
+        assert_eq!(semantic.layer_count(), 2);
+        assert_eq!(semantic.node(root_id)?.children().len(), NUM_SEMANTIC_NODES);
+        assert!(semantic.node(NUM_COOR_NODES)?.children().is_empty());
+        assert_eq!(semantic.cross_edges_from(NUM_COOR_NODES).len(), 1);
+        assert!(semantic.cross_edges_from(0).is_empty());
+
+        assert_eq!(sg.top_layers(5), sg);
+        assert_eq!(sg.top_layers(0).layer_count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn visible_subgraph_all() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let xs = [-2.0, -1.0, 0.0, 1.0, 2.0];
+        let nodes = xs.map(|x| sg.new_coordinates(x, 0.0, 2.0, vec![]));
+        let ids = nodes.each_ref().map(|n| n.id);
+        let room = sg.new_node(vec![]);
+        let room_id = room.id;
+        let layer = sg.new_layer();
+        for node in nodes {
+            layer.push_node(node)?;
+        }
+        sg.new_layer().push_node(room)?;
+        for nid in ids {
+            sg.nest(nid).under(room_id)?;
+        }
+
+        // two narrow cones looking at +Z, shifted along X so that they overlap around x = 0
+        let cone_at = |x| {
+            Observer::from_ypr(
+                Coordinate::new(x, 0.0, 0.0),
+                0.0,
+                0.0,
+                0.0,
+                25_f32.to_radians(),
+                0.6,
+                6.0,
+            )
+        };
+        let (left, right) = (cone_at(-0.5), cone_at(0.5));
+        let visible_ids = |sg: &SceneGraph| -> Vec<usize> {
+            ids.into_iter()
+                .filter(|nid| sg.node(*nid).is_ok())
+                .collect()
+        };
+        assert_eq!(visible_ids(&sg.visible_subgraph(left, room_id)?), ids[1..3]);
+        assert_eq!(
+            visible_ids(&sg.visible_subgraph(right, room_id)?),
+            ids[2..4]
+        );
+
+        let both = sg.visible_subgraph_all(&[left, right], room_id)?;
+        assert_eq!(visible_ids(&both), [ids[2]]);
+        assert!(both.node(room_id).is_ok());
+
+        let disjoint = sg.visible_subgraph_all(&[cone_at(-3.0), cone_at(3.0)], room_id)?;
+        assert!(visible_ids(&disjoint).is_empty());
+        assert!(disjoint.node(room_id).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn label_path() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
+        // coordinate nodes have no name, so only their parents are labeled
+        assert_eq!(sg.label_path(23, "name")?, vec!["root", "semantic 2"]);
+        assert_eq!(
+            sg.label_path(NUM_COOR_NODES + 2, "name")?,
+            vec!["root", "semantic 2"]
+        );
+        assert_eq!(sg.label_path(root_id, "name")?, vec!["root"]);
+        assert!(sg.label_path(23, "type")?.is_empty());
+        assert!(matches!(
+            sg.label_path(root_id + 1, "name"),
+            Err(AtlasError::NodeNotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn repair_hierarchy() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        assert_eq!(sg.repair_hierarchy(), 0);
+
+        // one-sided links: a child missing from its parent's children, a child listed under
+        // the wrong parent, a dangling child and a parent link to a nonexistent node
+        let semantic = NUM_COOR_NODES;
+        sg.node_mut(semantic)?.children.retain(|&cid| cid != 3);
+        sg.node_mut(semantic + 1)?.children.push(4);
+        sg.node_mut(semantic + 2)?.children.push(root_id + 10);
+        sg.node_mut(5)?.pid = Some(root_id + 10);
+
+        // node 5 is also dropped from the children of its former parent
+        assert_eq!(sg.repair_hierarchy(), 5);
+        assert!(sg.node(semantic)?.children().contains(&3));
+        assert!(!sg.node(semantic + 1)?.children().contains(&4));
+        assert!(!sg.node(semantic + 2)?.children().contains(&(root_id + 10)));
+        assert_eq!(sg.node(5)?.pid(), None);
+        assert!(!sg.node(semantic)?.children().contains(&5));
+        assert_eq!(sg.repair_hierarchy(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn union_layers() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        let (furniture, [chair, table, wall, clock]) = furniture()?;
+        let offset = 1 + root_id;
+        let mut other = furniture.clone();
+        let room = other.new_node(vec![Feature::new("name", "room")]);
+        let room_id = room.id;
+        other.new_layer().push_node(room)?;
+        other.nest(chair).under(room_id)?;
+        other.add_cross_edge(clock, room_id, "part of")?;
+
+        sg.union_layers(other)?;
+        assert_eq!(sg.layer_count(), 3);
+
+        // the first scene is intact
+        assert_eq!(
+            sg.dfs(root_id).count(),
+            1 + NUM_SEMANTIC_NODES + NUM_COOR_NODES
+        );
+        assert_eq!(sg.edges_from(0).len(), NUM_COOR_NODES);
+
+        // the second scene is intact, with offset IDs on the matching layers
+        for id in [chair, table, wall, clock] {
+            let node = sg.node(id + offset)?;
+            assert_eq!(sg.layer_of(id + offset)?, 0);
+            assert_eq!(node.features, furniture.node(id)?.features);
+            let dsts = |edges: Vec<Cow<Edge>>| edges.iter().map(|e| e.dst).collect::<Vec<_>>();
+            let expected = dsts(furniture.edges_from(id));
+            assert_eq!(
+                dsts(sg.edges_from(id + offset)),
+                expected.iter().map(|dst| dst + offset).collect::<Vec<_>>()
+            );
+        }
+        assert_eq!(sg.layer_of(room_id + offset)?, 1);
+        assert_eq!(sg.node(chair + offset)?.pid(), Some(room_id + offset));
+        assert_eq!(sg.node(room_id + offset)?.children(), &[chair + offset]);
+        assert_eq!(sg.cross_edges_from(clock + offset)[0].1, room_id + offset);
+
+        // new IDs do not collide with either scene
+        assert_eq!(sg.new_node(vec![]).id, room_id + offset + 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn boundary_nodes() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        // the cone's half-angle is 35°, at a depth of 2 its edge is at x = 2 * tan(35°) ≈ 1.4
+        let edge = sg.new_coordinates(1.35, 0.0, 2.0, vec![]);
+        let center = sg.new_coordinates(0.0, 0.0, 2.0, vec![]);
+        let outside = sg.new_coordinates(1.5, 0.0, 2.0, vec![]);
+        let far = sg.new_coordinates(0.0, 0.0, 5.95, vec![]);
+        let room = sg.new_node(vec![]);
+        let ids = [edge.id, center.id, outside.id, far.id];
+        let room_id = room.id;
+        let layer = sg.new_layer();
+        for node in [edge, center, outside, far] {
+            layer.push_node(node)?;
+        }
+        sg.new_layer().push_node(room)?;
+        for nid in ids {
+            sg.nest(nid).under(room_id)?;
+        }
+
+        let boundary = sg.boundary_nodes(cone(), room_id, 2_f32.to_radians(), 0.1)?;
+        assert_eq!(boundary, vec![ids[0], ids[3]]);
+        assert!(sg.boundary_nodes(cone(), room_id, 0.0, 0.0)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_layer() -> Result<()> {
+        let (mut sg, [chair, table, wall, clock]) = furniture()?;
+        let original = sg.clone();
+        let snapshot = sg.snapshot_layer(0)?;
+
+        sg.layer_mut(0)?.add_edge(chair, wall, "next to")?;
+        sg.layer_mut(0)?.del_edge(table, chair)?;
+        sg.node_mut(clock)?.features.clear();
+        let lamp = sg.new_node(vec![Feature::new("name", "lamp")]);
+        let lamp_id = lamp.id;
+        sg.layer_mut(0)?.push_node(lamp)?;
+        assert_ne!(sg.layer(0)?, original.layer(0)?);
+
+        sg.restore_layer(0, snapshot.clone())?;
+        assert_eq!(sg.layer(0)?, original.layer(0)?);
+        assert!(sg.node(lamp_id).is_err());
+
+        sg.freeze_layer(0)?;
+        assert!(matches!(
+            sg.restore_layer(0, snapshot.clone()),
+            Err(AtlasError::LayerFrozen)
+        ));
+        assert!(matches!(
+            sg.restore_layer(1, snapshot),
+            Err(AtlasError::LayerOutOfBounds(1, 1))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn leaves() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
+        let leaves = sg.leaves();
+        assert_eq!(leaves.len(), sg.layer(0)?.nodes.len());
+        assert!(leaves.iter().all(|n| n.coordinates.is_some()));
+
+        let under = sg.leaves_under(root_id)?;
+        assert_eq!(under.len(), NUM_COOR_NODES);
+        let under = sg.leaves_under(NUM_COOR_NODES + 3)?;
+        assert_eq!(
+            under.iter().map(|n| n.id).collect::<Vec<_>>(),
+            (30..40).collect::<Vec<_>>()
+        );
+        assert_eq!(sg.leaves_under(7)?.len(), 1);
+        assert!(matches!(
+            sg.leaves_under(root_id + 1),
+            Err(AtlasError::NodeNotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn visibility_delta() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let behind = sg.new_coordinates(0.0, 0.0, -2.0, vec![]);
+        let ahead = sg.new_coordinates(0.0, 0.0, 2.0, vec![]);
+        let far = sg.new_coordinates(0.0, 0.0, 5.0, vec![]);
+        let room = sg.new_node(vec![]);
+        let ids @ [behind_id, ahead_id, far_id] = [behind.id, ahead.id, far.id];
+        let room_id = room.id;
+        let layer = sg.new_layer();
+        for node in [behind, ahead, far] {
+            layer.push_node(node)?;
+        }
+        sg.new_layer().push_node(room)?;
+        for nid in ids {
+            sg.nest(nid).under(room_id)?;
+        }
+
+        // moving back by 3 along Z brings the node behind into view, and pushes the one far
+        // ahead beyond the far limit
+        let from = cone();
+        let to = Observer::from_ypr(
+            Coordinate::new(0.0, 0.0, -3.0),
+            0.0,
+            0.0,
+            0.0,
+            35_f32.to_radians(),
+            0.6,
+            6.0,
+        );
+        let (entered, left) = sg.visibility_delta(room_id, &from, &to)?;
+        assert_eq!(entered, vec![behind_id]);
+        assert_eq!(left, vec![far_id]);
+        assert!(sg.visibility_delta(room_id, &to, &to)?.0.is_empty());
+        assert_eq!(sg.visibility_delta(room_id, &to, &from)?, (left, entered));
+        assert!(sg.visible_subgraph(to, room_id)?.node(ahead_id).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_empty_layers() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        sg.new_layer();
+        sg.new_layer();
+        assert_eq!(sg.remove_empty_layers(), 2);
+        assert_eq!(sg.layer_count(), 3);
+        assert_eq!(sg.layer_of(root_id)?, 2);
+
+        // an interior empty layer with live links across it is kept
+        let (mut sg, [chair, ..]) = furniture()?;
+        sg.new_layer();
+        let room = sg.new_node(vec![Feature::new("name", "room")]);
+        let room_id = room.id;
+        sg.new_layer().push_node(room)?;
+        sg.node_mut(chair)?.pid = Some(room_id);
+        sg.node_mut(room_id)?.children.push(chair);
+        assert_eq!(sg.remove_empty_layers(), 0);
+        assert_eq!(sg.layer_count(), 3);
+
+        // once the links are gone, the interior layer is removed
+        sg.node_mut(chair)?.pid = None;
+        sg.node_mut(room_id)?.children.clear();
+        assert_eq!(sg.remove_empty_layers(), 1);
+        assert_eq!(sg.layer_of(room_id)?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn descendant_leaf_count() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
+        let counts = sg.leaf_counts_for_layer(1)?;
+        assert_eq!(counts.len(), NUM_SEMANTIC_NODES);
+        assert!(counts.values().all(|c| *c == 10));
+        assert_eq!(sg.descendant_leaf_count(NUM_COOR_NODES)?, 10);
+        assert_eq!(sg.descendant_leaf_count(root_id)?, NUM_COOR_NODES);
+        assert_eq!(sg.descendant_leaf_count(0)?, 0);
+        assert_eq!(sg.leaf_counts_for_layer(2)?[&root_id], NUM_COOR_NODES);
+        for lid in 0..3 {
+            for (nid, count) in sg.leaf_counts_for_layer(lid)? {
+                assert_eq!(count, sg.descendant_leaf_count(nid)?);
+            }
+        }
+        assert!(matches!(
+            sg.leaf_counts_for_layer(3),
+            Err(AtlasError::LayerOutOfBounds(3, 3))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn spawn_node() -> Result<()> {
+        let (mut sg, _) = furniture()?;
+
+        let lamp_id = sg.spawn_node(0, vec![Feature::new("name", "lamp")])?;
+        assert_eq!(sg.layer_of(lamp_id)?, 0);
+        assert_eq!(sg.node(lamp_id)?.feature("name")?, "lamp");
+
+        // failed spawns do not burn an ID
+        assert!(matches!(
+            sg.spawn_node(1, vec![]),
+            Err(AtlasError::LayerOutOfBounds(1, 1))
+        ));
+        sg.freeze_layer(0)?;
+        assert!(matches!(
+            sg.spawn_node(0, vec![]),
+            Err(AtlasError::LayerFrozen)
+        ));
+        sg.unfreeze_layer(0)?;
+        assert_eq!(sg.spawn_node(0, vec![])?, lamp_id + 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lowest_common_ancestor() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+
+        assert_eq!(sg.lowest_common_ancestor(3, 7)?, Some(NUM_COOR_NODES));
+        assert_eq!(sg.lowest_common_ancestor(3, 17)?, Some(root_id));
+        assert_eq!(
+            sg.lowest_common_ancestor(3, NUM_COOR_NODES + 1)?,
+            Some(root_id)
+        );
+        assert_eq!(
+            sg.lowest_common_ancestor(3, NUM_COOR_NODES)?,
+            Some(NUM_COOR_NODES)
+        );
+        assert_eq!(sg.lowest_common_ancestor(3, 3)?, Some(3));
+
+        let lone = sg.spawn_node(0, vec![])?;
+        assert_eq!(sg.lowest_common_ancestor(3, lone)?, None);
+        assert!(matches!(
+            sg.lowest_common_ancestor(3, lone + 1),
+            Err(AtlasError::NodeNotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn subtree_edges() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+        let semantic = NUM_COOR_NODES + 2;
+
+        let edges = sg.subtree_edges(semantic)?;
+        assert_eq!(edges.len(), 2);
+        // the semantic node's edges to its siblings are outside the subtree
+        assert_eq!(edges[1].len(), 1);
+        assert_eq!((edges[1][0].src, edges[1][0].dst), (semantic, semantic));
+        assert_eq!(edges[0].len(), 100);
+        assert!(
+            edges[0]
+                .iter()
+                .all(|e| (20..30).contains(&e.src) && (20..30).contains(&e.dst))
+        );
+
+        let edges = sg.subtree_edges(root_id)?;
+        let counts = edges.iter().map(|l| l.len()).collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            [
+                NUM_COOR_NODES * NUM_COOR_NODES,
+                NUM_SEMANTIC_NODES * NUM_SEMANTIC_NODES,
+                0
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn visible_subgraph_depth_limited() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        sg.recompute_coordinates_from_children(1)?;
+        let observer = cone();
+
+        let visible = sg.visible_subgraph_depth_limited(observer, root_id, 1)?;
+        assert_eq!(visible.layer_count(), 3);
+        // the coordinate layer is never reached
+        assert!(visible.layer(0)?.is_empty());
+        let expected = (NUM_COOR_NODES..NUM_COOR_NODES + NUM_SEMANTIC_NODES)
+            .filter(|&sid| {
+                let centroid = sg.node(sid).unwrap().coordinates.unwrap();
+                observer.observers(&centroid)
+            })
+            .collect::<Vec<_>>();
+        assert!(!expected.is_empty() && expected.len() < NUM_SEMANTIC_NODES);
+        let semantic = visible
+            .layer(1)?
+            .nodes
+            .iter()
+            .map(|n| n.id)
+            .collect::<Vec<_>>();
+        assert_eq!(semantic, expected);
+        assert!(visible.node(root_id).is_ok());
+
+        // without a depth limit, culling happens on the coordinate layer
+        let full = sg.visible_subgraph_depth_limited(observer, root_id, usize::MAX)?;
+        assert_eq!(full, sg.visible_subgraph(observer, root_id)?);
+
+        // the root alone has no representative point
+        let root_only = sg.visible_subgraph_depth_limited(observer, root_id, 0)?;
+        assert!(root_only.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_coordinates() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        let inside = Coordinate::new(0.0, 0.0, 1.0);
+        let outside = Coordinate::new(6.0, 6.0, 6.0);
+
+        // node 0 is inside the FOV and node 15 is outside, swap them
+        sg.set_coordinates(&[(0, outside), (15, inside)])?;
+        assert_eq!(sg.node(0)?.coordinates, Some(outside));
+        let visible = sg.visible_subgraph(cone(), root_id)?;
+        assert!(visible.node(0).is_err());
+        assert!(visible.node(15).is_ok());
+
+        // unknown IDs fail the whole update
+        assert!(matches!(
+            sg.set_coordinates(&[(0, inside), (usize::MAX, inside)]),
+            Err(AtlasError::NodeNotFound)
+        ));
+        assert_eq!(sg.node(0)?.coordinates, Some(outside));
+
+        Ok(())
+    }
+
+    #[test]
+    fn edges_crossing() -> Result<()> {
+        let (sg, [chair_id, table_id, wall_id, clock_id]) = furniture()?;
+        let furniture = HashSet::from([chair_id, table_id]);
+        let fixtures = HashSet::from([wall_id, clock_id]);
+        let layer = sg.layer(0)?;
+
+        let crossing = layer.edges_crossing(&furniture, &fixtures);
+        assert_eq!(crossing.len(), 1);
+        assert_eq!((crossing[0].src, crossing[0].dst), (table_id, wall_id));
+
+        let crossing = layer.edges_crossing(&fixtures, &furniture);
+        assert!(crossing.is_empty());
+
+        // edges within a set cross when both sides are the same set
+        assert_eq!(layer.edges_crossing(&furniture, &furniture).len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn degree_distribution() -> Result<()> {
+        let (sg, _) = fov_scene()?;
+
+        // every node links to all nodes of the layer, itself included, in both directions
+        let distribution = sg.layer(0)?.degree_distribution();
+        assert_eq!(
+            distribution,
+            HashMap::from([(2 * NUM_COOR_NODES, NUM_COOR_NODES)])
+        );
+
+        let (sg, _) = furniture()?;
+        let distribution = sg.layer(0)?.degree_distribution();
+        // chair: 1 + 1, table: 2 + 1, wall: 0 + 2, clock: 1 + 0
+        assert_eq!(distribution, HashMap::from([(1, 1), (2, 2), (3, 1)]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn content_eq() -> Result<()> {
+        let (sg, [chair_id, table_id, ..]) = furniture()?;
+        let chair = sg.node(chair_id)?;
+
+        // structure does not matter
+        let mut moved = chair.clone();
+        moved.pid = Some(42);
+        moved.children = vec![7];
+        moved.edges.iter_mut().for_each(|e| e.seq += 10);
+        assert!(chair.content_eq(&moved));
+
+        // features do
+        let mut repainted = chair.clone();
+        repainted.features = vec![Feature::new("name", "stool")];
+        assert_eq!(repainted.id, chair.id);
+        assert!(!chair.content_eq(&repainted));
+
+        // and so do edges
+        let mut detached = chair.clone();
+        detached.edges.clear();
+        assert!(!chair.content_eq(&detached));
+
+        // but not their order
+        let mut table = sg.node(table_id)?.clone();
+        let mut reordered = table.clone();
+        reordered.edges.reverse();
+        assert_ne!(reordered.edges, table.edges);
+        assert!(table.content_eq(&reordered));
+
+        // unlike their weights
+        let mut reweighted = table.clone();
+        reweighted.edges[0].weight = Some(2.5);
+        assert!(!table.content_eq(&reweighted));
+        table.edges[0].weight = Some(1.5);
+        assert!(!table.content_eq(&reweighted));
+        reweighted.edges[0].weight = Some(1.5);
+        assert!(table.content_eq(&reweighted));
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_node_kind() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let mut place = sg.new_coordinates(0.0, 0.0, 1.0, Vec::new());
+        place.points = vec![Point::new(Coordinate::new(0.0, 0.0, 1.0), [1.0; 3])];
+        let room = sg.new_node(vec![Feature::new("name", "room")]);
+        let (place_id, room_id) = (place.id, room.id);
+        let layer = sg.new_layer();
+        layer.push_node(place)?;
+        layer.push_node(room)?;
+
+        // metric to semantic drops the geometry
+        assert_eq!(sg.node(place_id)?.kind(), NodeKind::Metric);
+        sg.set_node_kind(place_id, NodeKind::Semantic)?;
+        assert_eq!(sg.node(place_id)?.kind(), NodeKind::Semantic);
+        assert!(sg.node(place_id)?.points.is_empty());
+
+        // semantic to metric needs something to position the node at
+        assert!(matches!(
+            sg.set_node_kind(room_id, NodeKind::Metric),
+            Err(AtlasError::CoordinatesRequired)
+        ));
+        assert_eq!(sg.node(room_id)?.kind(), NodeKind::Semantic);
+        sg.node_mut(room_id)?.points = vec![
+            Point::new(Coordinate::new(1.0, 0.0, 0.0), [0.0; 3]),
+            Point::new(Coordinate::new(3.0, 0.0, 0.0), [0.0; 3]),
+        ];
+        sg.set_node_kind(room_id, NodeKind::Metric)?;
+        assert_eq!(
+            sg.node(room_id)?.coordinates,
+            Some(Coordinate::new(2.0, 0.0, 0.0))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn coverage_ratio() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
+        assert_eq!(sg.coverage_ratio(Observer::everything(), root_id)?, 1.0);
+        let ratio = sg.coverage_ratio(cone(), root_id)?;
+        assert!(ratio > 0.0 && ratio < 1.0);
+
+        let wide = Observer::from_ypr(Coordinate::ZERO, 0.0, 0.0, 0.0, 1.0, 0.6, 6.0);
+        assert!(sg.coverage_ratio(wide, root_id)? > ratio);
+
+        let behind = Observer::from_ypr(
+            Coordinate::ZERO,
+            std::f32::consts::PI,
+            0.0,
+            0.0,
+            0.6,
+            0.6,
+            6.0,
+        );
+        assert_eq!(sg.coverage_ratio(behind, root_id)?, 0.0);
+
+        assert!(sg.coverage_ratio(cone(), usize::MAX).is_err());
+
+        // flat geometry is padded to the near distance instead of sampled on a single plane
+        let scene = |points: &[Coordinate]| -> Result<(SceneGraph, usize)> {
+            let mut sg = SceneGraph::default();
+            let nodes = points
+                .iter()
+                .map(|p| sg.new_coordinates(p.x, p.y, p.z, Vec::new()))
+                .collect::<Vec<_>>();
+            let ids = nodes.iter().map(|n| n.id).collect::<Vec<_>>();
+            let room = sg.new_node(Vec::new());
+            let room_id = room.id;
+            let layer = sg.new_layer();
+            for node in nodes {
+                layer.push_node(node)?;
+            }
+            sg.new_layer().push_node(room)?;
+            for id in ids {
+                sg.nest(id).under(room_id)?;
+            }
+            Ok((sg, room_id))
+        };
+        // a single node on the edge of the cone is partly covered
+        let edge = 3.0 * 35_f32.to_radians().tan();
+        let (single, room_id) = scene(&[Coordinate::new(edge, 0.0, 3.0)])?;
+        let ratio = single.coverage_ratio(cone(), room_id)?;
+        assert!(ratio > 0.25 && ratio < 0.75, "{ratio}");
+        let (single, room_id) = scene(&[Coordinate::new(0.0, 0.0, 3.0)])?;
+        assert_eq!(single.coverage_ratio(cone(), room_id)?, 1.0);
+        // a line of nodes through the cone, which covers the middle of it
+        let line = (-4..=4)
+            .map(|x| Coordinate::new(x as f32, 0.0, 3.0))
+            .collect::<Vec<_>>();
+        let (line, room_id) = scene(&line)?;
+        let ratio = line.coverage_ratio(cone(), room_id)?;
+        assert!((ratio - edge / 4.0).abs() < 0.1, "{ratio}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn find_coincident() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let a = sg.new_coordinates(1.0, 1.0, 1.0, Vec::new());
+        let far = sg.new_coordinates(5.0, 5.0, 5.0, Vec::new());
+        let b = sg.new_coordinates(1.05, 1.0, 1.0, Vec::new());
+        // within tolerance of `b` only, but still part of the same object
+        let c = sg.new_coordinates(1.12, 1.0, 1.0, Vec::new());
+        let ids = [a.id, b.id, c.id];
+        let layer = sg.new_layer();
+        for node in [a, far, b, c] {
+            layer.push_node(node)?;
+        }
+
+        assert_eq!(sg.find_coincident(0, 0.1)?, vec![ids.to_vec()]);
+        assert!(sg.find_coincident(0, 0.01)?.is_empty());
+        assert!(sg.find_coincident(1, 0.1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn induced_edges() -> Result<()> {
+        let (sg, [_, table_id, wall_id, clock_id]) = furniture()?;
+
+        let edges = sg.induced_edges(&HashSet::from([clock_id, wall_id]));
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].len(), 1);
+        assert_eq!((edges[0][0].src, edges[0][0].dst), (clock_id, wall_id));
+
+        assert!(sg.induced_edges(&HashSet::from([clock_id, table_id]))[0].is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn dedup_edges() -> Result<()> {
+        let (mut sg, [chair_id, table_id, wall_id, clock_id]) = furniture()?;
+        let layer = sg.layer_mut(0)?;
+        layer.add_edge(table_id, chair_id, "next to")?;
+        layer.add_edge(table_id, chair_id, "next to")?;
+        layer.add_edge(table_id, chair_id, "left of")?;
+        layer.add_edge(clock_id, wall_id, "supported by")?;
+
+        assert_eq!(sg.node_mut(table_id)?.dedup_edges(), 2);
+        assert_eq!(sg.edges_from(table_id).len(), 3);
+        assert_eq!(sg.dedup_all_edges(), 1);
+        assert_eq!(sg.dedup_all_edges(), 0);
+        assert_eq!(sg.edges_from(clock_id).len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn shortest_path() -> Result<()> {
+        let (sg, [chair_id, table_id, wall_id, clock_id]) = furniture()?;
+
+        assert_eq!(sg.shortest_path(clock_id, wall_id)?, [clock_id, wall_id]);
+        assert_eq!(
+            sg.shortest_path(chair_id, wall_id)?,
+            [chair_id, table_id, wall_id]
+        );
+        assert_eq!(sg.shortest_path(wall_id, wall_id)?, [wall_id]);
+
+        // edges are directed
+        assert!(matches!(
+            sg.shortest_path(wall_id, clock_id),
+            Err(AtlasError::NoPath(..))
+        ));
+        assert!(matches!(
+            sg.shortest_path(usize::MAX, clock_id),
+            Err(AtlasError::NodeNotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn shortest_path_edges() -> Result<()> {
+        let (sg, [chair_id, table_id, wall_id, clock_id]) = furniture()?;
+
+        let path = sg.shortest_path_edges(chair_id, wall_id)?;
+        let hops = path.iter().map(|e| (e.src, e.dst)).collect::<Vec<_>>();
+        assert_eq!(hops, [(chair_id, table_id), (table_id, wall_id)]);
+        assert_eq!(
+            path.iter().map(|e| e.desc.as_str()).collect::<Vec<_>>(),
+            ["next to", "in front of"]
+        );
+
+        assert!(sg.shortest_path_edges(clock_id, clock_id)?.is_empty());
+        assert!(matches!(
+            sg.shortest_path_edges(wall_id, clock_id),
+            Err(AtlasError::NoPath(src, dst)) if src == wall_id && dst == clock_id
+        ));
+        assert!(matches!(
+            sg.shortest_path_edges(chair_id, usize::MAX),
+            Err(AtlasError::NodeNotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn for_each_node_mut() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let chair = sg.new_node(vec![Feature::new("name", "Chair")]);
+        let room = sg.new_node(vec![Feature::new("name", "LIVING Room")]);
+        let (chair_id, room_id) = (chair.id, room.id);
+        sg.new_layer().push_node(chair)?;
+        sg.new_layer().push_node(room)?;
+
+        sg.for_each_node_mut(|n| {
+            for f in &mut n.features {
+                if f.key() == "name" {
+                    *f = Feature::new("name", &f.value().to_lowercase());
+                }
+            }
+        });
+
+        let chairs = sg.nodes_matching(&[&Feature::new("name", "chair")]);
+        assert_eq!(chairs[0].len(), 1);
+        assert_eq!(chairs[0][0].id, chair_id);
+        let rooms = sg.nodes_matching(&[&Feature::new("name", "living room")]);
+        assert_eq!(rooms[1].len(), 1);
+        assert_eq!(rooms[1][0].id, room_id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn layer_kinds() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let point = sg.new_coordinates(0.0, 0.0, 1.0, Vec::new());
+        let place = sg.new_coordinates(0.0, 0.0, 1.0, Vec::new());
+        let chair = sg.new_node(vec![Feature::new("name", "chair")]);
+        let (place_id, chair_id) = (place.id, chair.id);
+        assert!(matches!(
+            sg.metric_layer(),
+            Err(AtlasError::LayerKindNotFound(LayerKind::Metric))
+        ));
+
+        // an untagged layer below shifts the indices of the tagged ones
+        sg.new_layer().push_node(point)?;
+        let metric = sg.new_layer();
+        metric.set_kind(LayerKind::Metric);
+        metric.push_node(place)?;
+        let semantic = sg.new_layer();
+        semantic.set_kind(LayerKind::Semantic);
+        semantic.push_node(chair)?;
+        sg.nest(place_id).under(chair_id)?;
+
+        assert!(sg.metric_layer()?.node(place_id).is_ok());
+        assert!(sg.semantic_layer()?.node(chair_id).is_ok());
+        assert_eq!(sg.layer(0)?.kind(), None);
+
+        // tags survive deriving and reloading the graph
+        let sub = sg.subgraph(chair_id)?;
+        assert!(sub.metric_layer()?.node(place_id).is_ok());
+        let json = serde_json::to_string(&sg).unwrap();
+        let loaded: SceneGraph = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.semantic_layer()?.kind(), Some(LayerKind::Semantic));
+
+        Ok(())
+    }
+
+    #[test]
+    fn most_updated_nodes() -> Result<()> {
+        let (mut sg, [chair_id, table_id, wall_id, clock_id]) = furniture()?;
+
+        // the clock is observed three times, the table once
+        let updates = [clock_id, table_id, clock_id, clock_id];
+        for (time, nid) in updates.into_iter().enumerate() {
+            let mut update = Layer::new();
+            update.push_node(Node::new(
+                nid,
+                vec![Feature::new("time", &time.to_string())],
+                None,
+            ))?;
+            sg.merge_top_layer(update)?;
+        }
+
+        assert_eq!(sg.node(clock_id)?.update_count(), 3);
+        assert_eq!(sg.node(wall_id)?.update_count(), 0);
+        assert_eq!(sg.most_updated_nodes(2), [clock_id, table_id]);
+        assert_eq!(sg.most_updated_nodes(10).len(), 4);
+        assert_eq!(sg.most_updated_nodes(10)[2], chair_id.min(wall_id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn add_object() -> Result<()> {
+        let mut sg: SceneGraph = SceneGraph::new_metric_semantic();
+        let red = [1.0, 0.0, 0.0];
+        let chair_id = sg.add_object(
+            vec![Feature::new("name", "chair")],
+            &[([0.0, 0.0, 1.0], red), ([0.0, 0.5, 1.0], red)],
+        )?;
+        let lamp_id = sg.add_object(
+            vec![Feature::new("name", "lamp")],
+            &[([2.0, 2.0, 2.0], red)],
+        )?;
+
+        assert_eq!(sg.layer_count(), 2);
+        assert_eq!(sg.semantic_layer()?.nodes.len(), 2);
+        assert_eq!(sg.metric_layer()?.nodes.len(), 3);
+        assert_eq!(sg.layer_of(chair_id)?, 1);
+
+        let chair = sg.node(chair_id)?;
+        assert_eq!(chair.feature("name")?, "chair");
+        assert_eq!(chair.kind(), NodeKind::Semantic);
+        assert_eq!(chair.children.len(), 2);
+        for &cid in &chair.children {
+            let child = sg.node(cid)?;
+            assert_eq!(child.pid, Some(chair_id));
+            assert_eq!(child.points.len(), 1);
+            assert_eq!(child.points[0].color, red);
+            assert_eq!(child.coordinates, Some(child.points[0].position));
+        }
+        let lamp = sg.node(lamp_id)?;
+        assert_eq!(
+            sg.node(lamp.children[0])?.coordinates,
+            Some(Coordinate::new(2.0, 2.0, 2.0))
+        );
+
+        // adding an object is a mutation, even without points
+        let generation = sg.generation();
+        let empty_id = sg.add_object(vec![Feature::new("name", "ghost")], &[])?;
+        assert!(sg.generation() > generation);
+        assert!(sg.node(empty_id)?.children.is_empty());
+        assert_eq!(sg.semantic_layer()?.nodes.len(), 3);
+
+        // the graph needs the tagged layers
+        assert!(matches!(
+            SceneGraph::default().add_object(Vec::new(), &[]),
+            Err(AtlasError::LayerKindNotFound(LayerKind::Metric))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn clipped_edges() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+        let visible = sg.visible_subgraph(cone(), root_id)?;
+
+        let clipped = sg.clipped_edges(cone(), root_id)?;
+        for &(src, dst) in &clipped {
+            assert!(visible.node(src).is_err() || visible.node(dst).is_err());
+        }
+        // half of the coordinate nodes and 5 of the semantic nodes are culled
+        let visible_coords = NUM_COOR_NODES / 2;
+        let visible_semantic = NUM_SEMANTIC_NODES - 5;
+        assert_eq!(
+            clipped.len(),
+            NUM_COOR_NODES.pow(2) - visible_coords.pow(2) + NUM_SEMANTIC_NODES.pow(2)
+                - visible_semantic.pow(2)
+        );
+
+        assert!(
+            sg.clipped_edges(Observer::everything(), root_id)?
+                .is_empty()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn node_index() -> Result<()> {
+        const NODES_PER_LAYER: usize = 10_000 / 3;
+        // build the graph by pushing nodes through `layer_mut`, looking each one up right away,
+        // and time it against the same lookups done by scanning the layers
+        let build = |indexed: bool| -> Result<(SceneGraph, Duration)> {
+            let mut sg = SceneGraph::default();
+            let mut elapsed = Duration::ZERO;
+            for lid in 0..3 {
+                sg.new_layer();
+                for i in 0..NODES_PER_LAYER {
+                    let node = sg.new_coordinates(i as f32, lid as f32, 0.0, Vec::new());
+                    let nid = node.id;
+                    sg.layer_mut(lid)?.push_node(node)?;
+                    let start = Instant::now();
+                    let found = match indexed {
+                        true => sg.node(nid)?,
+                        false => (0..3)
+                            .find_map(|lid| sg.layer(lid).ok()?.node(nid).ok())
+                            .unwrap(),
+                    };
+                    elapsed += start.elapsed();
+                    assert_eq!(found.id, nid);
+                }
+            }
+            Ok((sg, elapsed))
+        };
+        let (_, scanned) = build(false)?;
+        let (mut sg, indexed) = build(true)?;
+        assert!(indexed < scanned, "{indexed:?} vs {scanned:?}");
+        // every node nests under the node with the same position one layer up
+        for lid in 0..2 {
+            for i in 0..NODES_PER_LAYER {
+                let nid = lid * NODES_PER_LAYER + i;
+                sg.nest(nid).under(nid + NODES_PER_LAYER)?;
+            }
+        }
+
+        let start = Instant::now();
+        for nid in 0..3 * NODES_PER_LAYER {
+            assert_eq!(sg.node(nid)?.id, nid);
+            assert_eq!(sg.layer_of(nid)?, nid / NODES_PER_LAYER);
+        }
+        let lookups = start.elapsed();
+        let start = Instant::now();
+        for nid in 0..3 * NODES_PER_LAYER {
+            let lid = (0..3).position(|lid| sg.layer(lid).is_ok_and(|l| l.node(nid).is_ok()));
+            assert_eq!(lid, Some(nid / NODES_PER_LAYER));
+        }
+        assert!(lookups < start.elapsed());
+        assert!(matches!(
+            sg.node(3 * NODES_PER_LAYER),
+            Err(AtlasError::NodeNotFound)
+        ));
+
+        // deleting a node removes its descendants and moves the following nodes
+        let top = 2 * NODES_PER_LAYER;
+        sg.del_node(top)?;
+        for nid in [0, NODES_PER_LAYER, top] {
+            assert!(matches!(sg.node(nid), Err(AtlasError::NodeNotFound)));
+        }
+        assert_eq!(sg.node(top + 1)?.id, top + 1);
+        assert_eq!(sg.layer_of(1)?, 0);
+
+        // derived graphs index their own layers
+        let sub = sg.subgraph(top + 1)?;
+        assert_eq!(sub.node(1)?.id, 1);
+        assert_eq!(sub.layer_of(top + 1)?, 2);
+        assert!(sub.node(2).is_err());
+        let visible = sg.visible_subgraph(Observer::everything(), top + 1)?;
+        assert_eq!(visible.node(NODES_PER_LAYER + 1)?.id, NODES_PER_LAYER + 1);
+
+        // nodes moved or renamed behind the graph's back are still found
+        sg.node_mut(top + 2)?.id = usize::MAX;
+        assert_eq!(sg.layer_of(usize::MAX)?, 2);
+        assert!(sg.node(top + 2).is_err());
+        sg.layer_mut(0)?.nodes.swap(0, 1);
+        assert_eq!(sg.node(2)?.id, 2);
+        // including ones in the middle of a layer, which the ends of the layer do not give away
+        sg.layer_mut(1)?.node_mut(NODES_PER_LAYER + 5)?.id = usize::MAX - 1;
+        assert_eq!(sg.node(usize::MAX - 1)?.id, usize::MAX - 1);
+        assert_eq!(sg.layer_of(usize::MAX - 1)?, 1);
+        assert!(matches!(
+            sg.node(NODES_PER_LAYER + 5),
+            Err(AtlasError::NodeNotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn apply_features() -> Result<()> {
+        let (mut sg, [chair_id, table_id, wall_id, clock_id]) = furniture()?;
+        let decor = || vec![Feature::new("type", "decor")];
+
+        let updates = HashMap::from([(clock_id, decor()), (chair_id, decor())]);
+        assert_eq!(sg.apply_features(updates)?, 2);
+        let decor_nodes = sg.nodes_matching(&[&Feature::new("type", "decor")]);
+        assert_eq!(decor_nodes[0].len(), 2);
+        assert_eq!(sg.node(chair_id)?.feature("name")?, "chair");
+        assert_eq!(sg.node(chair_id)?.feature_count(), 3);
+
+        // unknown nodes fail the whole update, unless skipped
+        let updates = HashMap::from([(table_id, decor()), (usize::MAX, decor())]);
+        assert!(matches!(
+            sg.apply_features(updates.clone()),
+            Err(AtlasError::NodeNotFound)
+        ));
+        assert_eq!(sg.node(table_id)?.feature("type")?, "furniture");
+        assert_eq!(sg.apply_known_features(updates)?, 1);
+        assert_eq!(sg.node(table_id)?.feature("type")?, "decor");
+
+        sg.freeze_layer(0)?;
+        let updates = HashMap::from([(wall_id, decor())]);
+        assert!(matches!(
+            sg.apply_features(updates),
+            Err(AtlasError::LayerFrozen)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn orphaned_child_refs() -> Result<()> {
+        let (mut sg, root_id) = fov_scene()?;
+        assert!(sg.orphaned_child_refs().is_empty());
+
+        // a dangling child, and a child on the wrong layer
+        let semantic_id = NUM_COOR_NODES;
+        sg.node_mut(semantic_id)?.children.push(usize::MAX);
+        sg.node_mut(root_id)?.children.push(0);
+
+        let mut orphaned = sg.orphaned_child_refs();
+        orphaned.sort();
+        assert_eq!(orphaned, [(semantic_id, usize::MAX), (root_id, 0)]);
+        assert_eq!(sg.repair_hierarchy(), 2);
+        assert!(sg.orphaned_child_refs().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn colorize_by_feature() -> Result<()> {
+        let (mut sg, [chair_id, table_id, wall_id, clock_id]) = furniture()?;
+        let white = [1.0; 3];
+        let red = [1.0, 0.0, 0.0];
+        for nid in [chair_id, table_id, wall_id] {
+            sg.node_mut(nid)?.points = vec![
+                Point::new(Coordinate::new(0.0, 0.0, nid as f32), white),
+                Point::new(Coordinate::new(1.0, 0.0, nid as f32), white),
+            ];
+        }
+
+        assert_eq!(sg.colorize_by_feature("type", "furniture", red)?, 2);
+        for nid in [chair_id, table_id] {
+            assert!(sg.node(nid)?.points.iter().all(|p| p.color == red));
+        }
+        assert!(sg.node(wall_id)?.points.iter().all(|p| p.color == white));
+
+        // matching nodes without a point cloud are not counted, and recoloring nothing does not
+        // count as a mutation
+        let generation = sg.generation();
+        assert_eq!(sg.colorize_by_feature("type", "appliance", red)?, 0);
+        assert!(sg.node(clock_id)?.points.is_empty());
+        assert_eq!(sg.colorize_by_feature("type", "lamp", red)?, 0);
+        assert_eq!(sg.generation(), generation);
+        assert_eq!(sg.colorize_by_feature("type", "furniture", white)?, 2);
+        assert_ne!(sg.generation(), generation);
+
+        Ok(())
+    }
+
+    #[test]
+    fn visible_edges() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
+        let edges = sg.visible_edges(cone(), root_id)?;
+        // half of the coordinate nodes and 10 of the semantic nodes are visible, and both
+        // layers are fully connected
+        assert_eq!(edges.len(), 3);
+        assert_eq!(edges[0].len(), (NUM_COOR_NODES / 2).pow(2));
+        assert_eq!(edges[1].len(), 10_usize.pow(2));
+        assert!(edges[2].is_empty());
+
+        let visible = sg.visible_subgraph(cone(), root_id)?;
+        for (lid, layer_edges) in edges.iter().enumerate() {
+            assert_eq!(
+                layer_edges.len(),
+                visible.layer(lid)?.edges_matching("connect").len()
+            );
+        }
+
+        assert!(sg.visible_edges(cone(), usize::MAX).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn visible_edges_above_bottom_layer() -> Result<()> {
+        let (mut sg, _) = fov_scene()?;
+        // a root whose subtree stops at the semantic layer, with positioned nodes in view
+        let a = sg.new_coordinates(0.0, 0.0, 1.0, Vec::new());
+        let b = sg.new_coordinates(0.0, 0.0, 2.0, Vec::new());
+        let (a_id, b_id) = (a.id, b.id);
+        let layer = sg.layer_mut(1)?;
+        layer.push_node(a)?;
+        layer.push_node(b)?;
+        layer.add_edge(a_id, b_id, "connect")?;
+        let root_id = sg.spawn_node(2, vec![Feature::new("name", "annex")])?;
+        sg.nest(a_id).under(root_id)?;
+        sg.nest(b_id).under(root_id)?;
+
+        // nothing in the subtree is on the coordinate layer, so nothing is visible
+        let visible = sg.visible_subgraph(cone(), root_id)?;
+        let edges = sg.visible_edges(cone(), root_id)?;
+        assert_eq!(edges.len(), 3);
+        for (lid, layer_edges) in edges.iter().enumerate() {
+            assert!(layer_edges.is_empty());
+            assert!(visible.layer(lid)?.edges_matching("connect").is_empty());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sparse_fov() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+        let mut sparse = sg.clone();
+        sparse.layer_mut(0)?.set_edge_storage(EdgeStorage::Sparse);
+
+        let visible = sg.visible_subgraph(cone(), root_id)?;
+        let sparse_visible = sparse.visible_subgraph(cone(), root_id)?;
+        assert_eq!(sparse_visible.layer(0)?.edge_storage(), EdgeStorage::Sparse);
+        assert_eq!(
+            sparse_visible.layer(0)?.edges_matching("connect").len(),
+            visible.layer(0)?.edges_matching("connect").len()
+        );
+        assert_eq!(
+            sparse.visible_edges(cone(), root_id)?[0].len(),
+            sg.visible_edges(cone(), root_id)?[0].len()
+        );
+        assert_eq!(sparse.edges_from(0).len(), sg.edges_from(0).len());
+        assert_eq!(sparse.edge_desc_histogram(), sg.edge_desc_histogram());
+
+        // merging a clone back keeps the storage and the edges
+        sparse.merge(sparse.clone())?;
+        assert_eq!(sparse.layer(0)?.edge_storage(), EdgeStorage::Sparse);
+        assert_eq!(sparse.edges_from(0).len(), sg.edges_from(0).len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_back_after_both_grew() -> Result<()> {
+        let (mut sg, [chair_id, table_id, wall_id, clock_id]) = furniture()?;
+        let mut sub = sg.subgraph(table_id)?;
+
+        // both graphs create a node after the subgraph was taken
+        let lamp = sub.new_node(vec![Feature::new("name", "lamp")]);
+        let lamp_id = lamp.id;
+        sub.layer_mut(0)?.push_node(lamp)?;
+        sub.layer_mut(0)?.add_edge(lamp_id, table_id, "on top of")?;
+        sub.node_mut(table_id)?
+            .set_feature(Feature::new("color", "red"));
+        let vase_id = sg.spawn_node(0, vec![Feature::new("name", "vase")])?;
+        assert_eq!(vase_id, lamp_id);
+
+        // cloning the subgraph keeps track of where it was derived
+        let sub = sub.clone();
+        let before = sg.layer(0)?.nodes.len();
+        let remapped = sg.merge(sub)?;
+        assert_eq!(sg.layer(0)?.nodes.len(), before + 1);
+        assert_eq!(sg.node(vase_id)?.feature("name")?, "vase");
+        assert!(sg.edges_from(vase_id).is_empty());
+
+        // the lamp got a fresh ID, along with its edge
+        let lamp = &sg.nodes_matching(&[&Feature::new("name", "lamp")])[0];
+        assert_eq!(lamp.len(), 1);
+        let new_lamp_id = lamp[0].id;
+        assert!(![chair_id, table_id, wall_id, clock_id, vase_id].contains(&new_lamp_id));
+        assert_eq!(remapped, HashMap::from([(lamp_id, new_lamp_id)]));
+        assert_eq!(
+            sg.edges_from(new_lamp_id)
+                .iter()
+                .map(|e| (e.src, e.dst))
+                .collect::<Vec<_>>(),
+            [(new_lamp_id, table_id)]
+        );
+        // nodes the subgraph took from the graph are still matched
+        assert_eq!(sg.node(table_id)?.feature("color")?, "red");
+
+        // the node counter is past every ID
+        let next = sg.new_node(Vec::new());
+        assert!(next.id > new_lamp_id && next.id > vase_id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn visible_everything() -> Result<()> {
+        let (sg, root_id) = fov_scene()?;
+
+        let subgraph = sg.subgraph(root_id)?;
+        let visible = sg.visible_subgraph(Observer::everything(), root_id)?;
+        assert_eq!(visible.layer(0)?.nodes.len(), NUM_COOR_NODES);
+        for lid in 0..3 {
+            let ids = |g: &SceneGraph| {
+                g.layer(lid)
+                    .map(|l| l.nodes.iter().map(|n| n.id).collect::<HashSet<_>>())
+            };
+            assert_eq!(ids(&visible)?, ids(&subgraph)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn visible_sorted_by_depth() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let far = sg.new_coordinates(0.0, 0.0, 3.0, Vec::new());
+        let near = sg.new_coordinates(0.2, 0.0, 1.0, Vec::new());
+        let mid = sg.new_coordinates(0.0, -0.3, 2.0, Vec::new());
+        let hidden = sg.new_coordinates(0.0, 0.0, -2.0, Vec::new());
+        let root = sg.new_node(vec![Feature::new("name", "root")]);
+        let ids = [far.id, near.id, mid.id, hidden.id];
+        let root_id = root.id;
+
+        let layer = sg.new_layer();
+        layer.push_node(far)?;
+        layer.push_node(near)?;
+        layer.push_node(mid)?;
+        layer.push_node(hidden)?;
+        sg.new_layer().push_node(root)?;
+        for id in ids {
+            sg.nest(id).under(root_id)?;
+        }
+
+        let sorted = sg.visible_sorted_by_depth(cone(), root_id)?;
+        assert_eq!(sorted, vec![ids[1], ids[2], ids[0]]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn k_nearest_visible() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let root = sg.new_node(vec![Feature::new("name", "root")]);
+        let root_id = root.id;
+        let mut ids = Vec::new();
+        let layer_nodes =
+            [3.0, 1.0, -2.0, 5.0, 2.0].map(|z| sg.new_coordinates(0.0, 0.0, z, Vec::new()));
+        let layer = sg.new_layer();
+        for node in layer_nodes {
+            ids.push(node.id);
+            layer.push_node(node)?;
+        }
+        sg.new_layer().push_node(root)?;
+        for &id in &ids {
+            sg.nest(id).under(root_id)?;
+        }
+
+        // the node behind the observer is never returned
+        assert_eq!(sg.k_nearest_visible(cone(), root_id, 2)?, [ids[1], ids[4]]);
+        assert_eq!(
+            sg.k_nearest_visible(cone(), root_id, 10)?,
+            [ids[1], ids[4], ids[0], ids[3]]
+        );
+        assert!(sg.k_nearest_visible(cone(), root_id, 0)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn visible_local() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let ahead = sg.new_coordinates(0.0, 0.0, 2.0, Vec::new());
+        let right = sg.new_coordinates(3.0, 0.5, 0.0, Vec::new());
+        let root = sg.new_node(vec![Feature::new("name", "root")]);
+        let (ahead_id, right_id, root_id) = (ahead.id, right.id, root.id);
+        let layer = sg.new_layer();
+        layer.push_node(ahead)?;
+        layer.push_node(right)?;
+        sg.new_layer().push_node(root)?;
+        sg.nest(ahead_id).under(root_id)?;
+        sg.nest(right_id).under(root_id)?;
+
+        let local = sg.visible_local(cone(), root_id)?;
+        assert_eq!(local, [(ahead_id, Coordinate::new(0.0, 0.0, 2.0))]);
+
+        // turned towards +X and moved back, the other node is now ahead
+        let half_angle = 35_f32.to_radians();
+        let pos = Coordinate::new(-1.0, 0.0, 0.0);
+        let turned = Observer::from_ypr(
+            pos,
+            std::f32::consts::FRAC_PI_2,
+            0.0,
+            0.0,
+            half_angle,
+            0.6,
+            6.0,
+        );
+        let local = sg.visible_local(turned, root_id)?;
+        assert_eq!(local.len(), 1);
+        assert_eq!(local[0].0, right_id);
+        assert!(local[0].1.abs_diff_eq(Coordinate::new(0.0, 0.5, 4.0), 1e-5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn nearest_observable() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let far = sg.new_coordinates(0.0, 0.0, 3.0, Vec::new());
+        let near = sg.new_coordinates(0.2, 0.0, 1.0, Vec::new());
+        let hidden = sg.new_coordinates(0.0, 0.0, 0.3, Vec::new());
+        let root = sg.new_node(vec![Feature::new("name", "root")]);
+        let ids = [far.id, near.id, hidden.id];
+        let root_id = root.id;
+
+        let layer = sg.new_layer();
+        layer.push_node(far)?;
+        layer.push_node(near)?;
+        layer.push_node(hidden)?;
+        sg.new_layer().push_node(root)?;
+        for id in ids {
+            sg.nest(id).under(root_id)?;
+        }
+
+        assert_eq!(sg.nearest_observable(cone(), root_id)?, Some(ids[1]));
+
+        sg.del_node(ids[0])?;
+        sg.del_node(ids[1])?;
+        assert_eq!(sg.nearest_observable(cone(), root_id)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn observers_of() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let point = sg.new_coordinates(0.0, 0.0, 2.0, Vec::new());
+        let room = sg.new_node(vec![Feature::new("name", "room")]);
+        let (point_id, room_id) = (point.id, room.id);
+        let layer = sg.new_layer();
+        layer.push_node(point)?;
+        layer.push_node(room)?;
+
+        // the second camera is looking the other way
+        let front = cone();
+        let back = Observer::from_ypr(
+            Coordinate::ZERO,
+            180_f32.to_radians(),
+            0.0,
+            0.0,
+            35_f32.to_radians(),
+            0.6,
+            6.0,
+        );
+        assert_eq!(sg.observers_of(point_id, &[back, front])?, vec![1]);
+        assert_eq!(
+            sg.observers_of(point_id, &[front, back, front])?,
+            vec![0, 2]
+        );
+        assert!(sg.observers_of(point_id, &[])?.is_empty());
+        assert!(matches!(
+            sg.observers_of(room_id, &[front]),
+            Err(AtlasError::CoordinatesRequired)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn visibility_map() -> Result<()> {
+        let mut sg = SceneGraph::default();
+        let inside = sg.new_coordinates(0.0, 0.0, 1.0, Vec::new());
+        let outside = sg.new_coordinates(6.0, 6.0, 6.0, Vec::new());
+        let unpositioned = sg.new_node(vec![Feature::new("name", "room")]);
+        let upper_inside = sg.new_coordinates(0.0, 0.5, 2.0, Vec::new());
+        let (inside_id, outside_id) = (inside.id, outside.id);
+        let (unpositioned_id, upper_inside_id) = (unpositioned.id, upper_inside.id);
+
+        let layer = sg.new_layer();
+        layer.push_node(inside)?;
+        layer.push_node(outside)?;
+        let layer = sg.new_layer();
+        layer.push_node(unpositioned)?;
+        layer.push_node(upper_inside)?;
+
+        let map = sg.visibility_map(cone());
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&inside_id), Some(&true));
+        assert_eq!(map.get(&outside_id), Some(&false));
+        assert_eq!(map.get(&upper_inside_id), Some(&true));
+        // nodes without coordinates are not reported
+        assert_eq!(map.get(&unpositioned_id), None);
+
+        Ok(())
+    }
 }
@@ -1,3 +1,7 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
 use crate::error::{AtlasError, Result};
 
 /// A node in the scene graph.
@@ -5,10 +9,12 @@ use crate::error::{AtlasError, Result};
 /// multiple edges to other nodes in the same layer. Nodes can also have parent-child relationships
 /// with nodes in the layers directly above or below them.
 /// Each node can hold a set of features, which are key-value pairs that provide additional
-/// information about the node. Nodes also support storeing 3D coordinates which can be used for
-/// Field-of-View calculations or spatial queries.
-#[derive(Debug, Clone)]
-pub struct Node {
+/// information about the node. Nodes also support storeing 3D coordinates and a point cloud which
+/// can be used for Field-of-View calculations or spatial queries.
+/// Beyond features, each node carries a user defined payload of type `T` (e.g. mesh or database
+/// handles), which defaults to `()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node<T = ()> {
     /// Unique identifier for the node.
     pub id: usize,
     /// Parent node Id, if node is nested under another node.
@@ -16,16 +22,40 @@ pub struct Node {
     /// Child node Ids from the lower layer, if node has nested nodes under it.
     pub(super) children: Vec<usize>,
     /// Edges to other nodes in the same layer.
+    /// Always empty for the nodes of a layer using [`EdgeStorage::Sparse`], which holds their
+    /// edges itself.
+    ///
+    /// [`EdgeStorage::Sparse`]: super::EdgeStorage::Sparse
     pub edges: Vec<Edge>,
     /// Features associated with the node.
     pub features: Vec<Feature>,
     /// Optional 3D coordinates of the node.
     pub coordinates: Option<Coordinate>,
+    /// Point cloud describing the geometry of the node, if any.
+    #[serde(default)]
+    pub points: Vec<Point>,
+    /// User data attached to the node.
+    pub data: T,
+    /// Number of merges that updated the node.
+    #[serde(default)]
+    pub(super) update_count: u64,
 }
 
 impl Node {
     /// Create a new Node with the given id, features, and optional coordinates.
     pub fn new(id: usize, features: Vec<Feature>, coordinates: Option<Coordinate>) -> Self {
+        Self::with_data(id, features, coordinates, ())
+    }
+}
+
+impl<T> Node<T> {
+    /// Create a new Node with the given id, features, optional coordinates and user data.
+    pub fn with_data(
+        id: usize,
+        features: Vec<Feature>,
+        coordinates: Option<Coordinate>,
+        data: T,
+    ) -> Self {
         Self {
             id,
             pid: None,
@@ -33,13 +63,97 @@ impl Node {
             edges: Vec::new(),
             features,
             coordinates,
+            points: Vec::new(),
+            data,
+            update_count: 0,
+        }
+    }
+
+    /// Get the point representing the node in space: the centroid of its point cloud if it has
+    /// one, its coordinates otherwise.
+    pub fn representative_point(&self) -> Option<Coordinate> {
+        if self.points.is_empty() {
+            return self.coordinates;
+        }
+        let sum = self.points.iter().map(|p| p.position).sum::<Coordinate>();
+        Some(sum / self.points.len() as f32)
+    }
+
+    /// Check whether two nodes carry the same content: features, coordinates and edges, where
+    /// edges are compared by destination, description and weight regardless of their order.
+    /// Structural fields (`id`, parent and children), edge sequence numbers and the user data
+    /// are ignored, so this tells whether the data of a node changed rather than its identity.
+    /// Only the edges held by the nodes are compared, so the edges of nodes on a layer using
+    /// [`EdgeStorage::Sparse`](super::EdgeStorage::Sparse) are not.
+    pub fn content_eq<U>(&self, other: &Node<U>) -> bool {
+        fn sorted(edges: &[Edge]) -> Vec<&Edge> {
+            let mut edges = edges.iter().collect::<Vec<_>>();
+            edges.sort_by(|e1, e2| {
+                (e1.dst, &e1.desc).cmp(&(e2.dst, &e2.desc)).then_with(|| {
+                    match (e1.weight, e2.weight) {
+                        (Some(w1), Some(w2)) => w1.total_cmp(&w2),
+                        (w1, w2) => w1.is_some().cmp(&w2.is_some()),
+                    }
+                })
+            });
+            edges
+        }
+
+        self.features == other.features
+            && self.coordinates == other.coordinates
+            && self.edges.len() == other.edges.len()
+            && sorted(&self.edges)
+                .into_iter()
+                .zip(sorted(&other.edges))
+                .all(|(e1, e2)| e1.dst == e2.dst && e1.desc == e2.desc && e1.weight == e2.weight)
+    }
+
+    /// Get the kind of the node: [`Metric`](NodeKind::Metric) if it has coordinates,
+    /// [`Semantic`](NodeKind::Semantic) otherwise.
+    pub fn kind(&self) -> NodeKind {
+        if self.coordinates.is_some() {
+            NodeKind::Metric
+        } else {
+            NodeKind::Semantic
         }
     }
+
+    /// Number of times the node was updated by a merge, e.g. to find frequently changing
+    /// objects in a perception stream.
+    pub fn update_count(&self) -> u64 {
+        self.update_count
+    }
+
     /// Check if the node has a feature with the specified key.
     pub fn has_feature(&self, key: &str) -> bool {
         self.features.iter().any(|f| f.key == key)
     }
 
+    /// Check if the node has a feature for every one of the specified keys.
+    pub fn has_all_features(&self, keys: &[&str]) -> bool {
+        keys.iter().all(|key| self.has_feature(key))
+    }
+
+    /// Reserve capacity for `n` more edges, e.g. before connecting the node to many others in a
+    /// dense layer.
+    pub fn reserve_edges(&mut self, n: usize) {
+        self.edges.reserve_exact(n);
+    }
+
+    /// Remove duplicate edges, keeping the first edge for every `(dst, desc)` pair.
+    /// Returns the number of removed edges.
+    pub fn dedup_edges(&mut self) -> usize {
+        let before = self.edges.len();
+        let mut seen = HashSet::new();
+        self.edges.retain(|e| seen.insert((e.dst, e.desc.clone())));
+        before - self.edges.len()
+    }
+
+    /// Number of features associated with the node.
+    pub fn feature_count(&self) -> usize {
+        self.features.len()
+    }
+
     /// Check if the node has the exact key-value pair as a feature.
     pub fn match_feature(&self, f: &Feature) -> bool {
         self.features.contains(f)
@@ -54,6 +168,16 @@ impl Node {
             .ok_or_else(|| AtlasError::FeatureNotFound(key.to_string()))
     }
 
+    /// Parent node Id, if node is nested under another node.
+    pub fn pid(&self) -> Option<usize> {
+        self.pid
+    }
+
+    /// Child node Ids from the lower layer.
+    pub fn children(&self) -> &[usize] {
+        &self.children
+    }
+
     pub(super) fn remove_child(&mut self, nid: usize) -> Result<()> {
         let index = self
             .children
@@ -71,21 +195,39 @@ impl Node {
         }
     }
 
-    pub fn merge(&mut self, mergee: Node) -> Result<()> {
+    /// Merge another node into this one using the default [`MergePolicy`].
+    pub fn merge(&mut self, mergee: Node<T>) -> Result<()> {
+        self.merge_with(mergee, MergePolicy::default())
+    }
+
+    /// Merge another node into this one.
+    /// Features of the mergee are set on this node and its coordinates and data take over.
+    /// The point cloud of the mergee is appended to the one of this node.
+    /// Edges are reconciled according to the given `policy`.
+    pub fn merge_with(&mut self, mergee: Node<T>, policy: MergePolicy) -> Result<()> {
         mergee.features.into_iter().for_each(|feature| {
             self.set_feature(feature);
         });
+        self.points.extend(mergee.points);
         self.coordinates = mergee.coordinates;
-        for mergee_edge in mergee.edges {
-            match self.edges.iter_mut().find(|e| e.dst == mergee_edge.dst) {
-                Some(e) => e.desc = mergee_edge.desc,
-                None => self.edges.push(mergee_edge),
+        self.data = mergee.data;
+        self.update_count += 1;
+        match policy {
+            MergePolicy::UnionEdges => {
+                for mergee_edge in mergee.edges {
+                    match self.edges.iter_mut().find(|e| e.dst == mergee_edge.dst) {
+                        Some(e) => e.desc = mergee_edge.desc,
+                        None => self.edges.push(mergee_edge),
+                    }
+                }
             }
+            MergePolicy::ReplaceEdges => self.edges = mergee.edges,
         }
         Ok(())
     }
 
-    fn set_feature(&mut self, feature: Feature) {
+    /// Set a feature, replacing the value of an existing feature with the same key.
+    pub(super) fn set_feature(&mut self, feature: Feature) {
         if !self.has_feature(&feature.key) {
             self.features.push(feature);
         } else {
@@ -99,12 +241,49 @@ impl Node {
     }
 }
 
+/// Kind of a node, derived from whether it is positioned in space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// A node with coordinates, e.g. a place or an object instance.
+    Metric,
+    /// A node without coordinates, e.g. a room or an object category.
+    Semantic,
+}
+
+/// Strategy for reconciling the edges of two nodes with the same ID during a merge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keep existing edges, update the description of edges present in both nodes and add
+    /// the new ones.
+    #[default]
+    UnionEdges,
+    /// Discard existing edges in favor of the incoming node's edges.
+    /// Useful for full state refreshes where a re-observation should correct stale edges.
+    ReplaceEdges,
+}
+
 /// 3D Coordinate type for representing spacial positions.
-/// The coordinate system is right-handed with Y-up convention.
+/// The coordinate system is right-handed with Y-up convention by default; observers can be
+/// placed in a Z-up world through [`CoordinateConvention`](super::CoordinateConvention).
 pub type Coordinate = glam::Vec3;
 
+/// A point of a point cloud, with its position and RGB color.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    /// Position of the point.
+    pub position: Coordinate,
+    /// RGB color of the point, each channel in `[0, 1]`.
+    pub color: [f32; 3],
+}
+
+impl Point {
+    pub fn new(position: Coordinate, color: [f32; 3]) -> Self {
+        Self { position, color }
+    }
+}
+
 /// A feature associated with a node, represented as a key-value pair.
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Feature {
     /// Key of the feature.
     key: String,
@@ -119,10 +298,25 @@ impl Feature {
             value: value.to_string(),
         }
     }
+
+    /// Key of the feature.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Value of the feature.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Value of the feature parsed as a number, if it is one.
+    pub fn as_f64(&self) -> Option<f64> {
+        self.value.trim().parse().ok()
+    }
 }
 
 /// An edge connecting two nodes in the same layer.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Edge {
     /// Source node ID.
     pub src: usize,
@@ -130,6 +324,12 @@ pub struct Edge {
     pub dst: usize,
     /// Description of the edge.
     pub desc: String,
+    /// Sequence number assigned when the edge is added to a layer.
+    #[serde(default)]
+    pub(super) seq: u64,
+    /// Distance or cost of traversing the edge, if known.
+    #[serde(default)]
+    pub weight: Option<f32>,
 }
 
 impl Edge {
@@ -138,6 +338,21 @@ impl Edge {
             src,
             dst,
             desc: desc.to_string(),
+            seq: 0,
+            weight: None,
         }
     }
+
+    /// Set the weight of the edge.
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+
+    /// Sequence number of the edge, increasing in the order edges were added to their layer with
+    /// [`Layer::add_edge`](super::Layer::add_edge). Edges created otherwise have a sequence
+    /// number of 0.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
 }
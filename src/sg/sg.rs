@@ -1,6 +1,16 @@
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
 
-use super::{Coordinate, Edge, Feature, Layer, Node, Observer};
+use serde::{Deserialize, Serialize};
+
+use super::index::NodeIndex;
+use super::{
+    Coordinate, Edge, EdgeStorage, Feature, Layer, LayerKind, MergePolicy, MergeReport, Node,
+    NodeKind, Observer, Point,
+};
 use crate::error::{AtlasError, Result};
 
 /// A hierarchical representation of objects and their relationships in a 3D environment.
@@ -10,19 +20,102 @@ use crate::error::{AtlasError, Result};
 ///
 /// The scene graph supports operations such as adding/removing nodes and edges,
 /// nesting nodes under other nodes, and querying nodes by their IDs.
-#[derive(Debug, Default, Clone)]
-pub struct SceneGraph {
+///
+/// Nodes can carry a user defined payload of type `T` (see [`Node::data`]), which defaults to `()`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneGraph<T = ()> {
     /// Layers of the scene graph, where each layer is either a Semantic or a Physical
     /// representation of the scene.
-    layers: Vec<Layer>,
+    layers: Vec<Layer<T>>,
 
     /// Counter to assign unique IDs to nodes.
+    /// It is always greater than every ID allocated in this graph or in any graph it was derived
+    /// from, so graphs derived through [`subgraph`](SceneGraph::subgraph) or
+    /// [`visible_subgraph`](SceneGraph::visible_subgraph) inherit it from their source, even
+    /// though they hold fewer nodes.
+    /// It is serialized along with the layers, so nodes created after a reload never reuse IDs.
     node_counter: usize,
+
+    /// Node counter of the graph this one was derived from at the time it was derived, if any.
+    /// Nodes with IDs from there on were created after the derivation, which lets
+    /// [`merge`](SceneGraph::merge) tell them apart from the nodes created in the meantime in
+    /// the graph it is merged back into.
+    #[serde(default)]
+    derived_at: Option<usize>,
+
+    /// Directed edges between nodes on any layers, as `(src, dst, desc)`, on top of the
+    /// intra-layer edges and the parent/child hierarchy. They never form a cycle.
+    #[serde(default)]
+    cross_edges: Vec<(usize, usize, String)>,
+
+    /// Counter bumped on every mutation of the scene graph.
+    #[serde(skip)]
+    generation: u64,
+
+    /// Index from node IDs to their location in `layers`, for the current generation.
+    #[serde(skip)]
+    index: NodeIndex,
+}
+
+/// Scene graphs are equal if they hold the same layers, cross edges and node counter, regardless
+/// of their [`generation`](SceneGraph::generation).
+impl<T: PartialEq> PartialEq for SceneGraph<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.layers == other.layers
+            && self.cross_edges == other.cross_edges
+            && self.node_counter == other.node_counter
+    }
 }
 
-impl SceneGraph {
+impl Default for SceneGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SceneGraph<T> {
+    /// Create an empty scene graph whose nodes carry a payload of type `T`.
+    /// For the default `()` payload, [`SceneGraph::default`] can be used instead.
+    pub fn new() -> Self {
+        Self {
+            layers: Vec::new(),
+            node_counter: 0,
+            derived_at: None,
+            cross_edges: Vec::new(),
+            generation: 0,
+            index: NodeIndex::default(),
+        }
+    }
+
+    /// Create a scene graph with a [`Metric`](LayerKind::Metric) layer at the bottom and a
+    /// [`Semantic`](LayerKind::Semantic) layer above it, to be filled with
+    /// [`add_object`](SceneGraph::add_object).
+    pub fn new_metric_semantic() -> Self {
+        let mut sg = Self::new();
+        sg.new_layer().set_kind(LayerKind::Metric);
+        sg.new_layer().set_kind(LayerKind::Semantic);
+        sg
+    }
+
+    /// Get the generation of the scene graph.
+    /// The generation changes whenever the scene graph is mutated and stays the same across
+    /// queries, so it can be used as a key to cache query results.
+    ///
+    /// Since changes made through a mutable reference to a layer or a node can not be tracked,
+    /// handing out such a reference (e.g. [`layer_mut`](SceneGraph::layer_mut) or
+    /// [`new_layer`](SceneGraph::new_layer)) counts as a mutation.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Record a mutation of the scene graph.
+    fn touch(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
     /// Create a new layer and add it to the scene graph.
-    pub fn new_layer(&mut self) -> &mut Layer {
+    pub fn new_layer(&mut self) -> &mut Layer<T> {
+        self.touch();
         self.layers.push(Layer::new());
         self.layers.last_mut().unwrap()
     }
@@ -30,7 +123,24 @@ impl SceneGraph {
     /// Create a subgraph rooted at the specified node ID.
     /// The subgraph includes the specified node and all its descendants.
     /// If the node is not found, an error is returned.
-    fn subgraph(&self, root_node_id: usize) -> Result<SceneGraph> {
+    ///
+    /// The subgraph keeps the layer indices of the original graph up to the root's layer and
+    /// inherits its node counter, so nodes created in the subgraph get IDs that do not collide
+    /// with any node of the original graph and the subgraph can be [`merge`](SceneGraph::merge)d
+    /// back into it.
+    pub fn subgraph(&self, root_node_id: usize) -> Result<Self>
+    where
+        T: Clone,
+    {
+        self.subgraph_depth_limited(root_node_id, usize::MAX)
+    }
+
+    /// Create a subgraph like [`subgraph`](SceneGraph::subgraph) that only descends `max_depth`
+    /// layers below the root node. The layers below that are left empty.
+    fn subgraph_depth_limited(&self, root_node_id: usize, max_depth: usize) -> Result<Self>
+    where
+        T: Clone,
+    {
         let mut layers = Vec::new();
         let mut nodes_to_visit = vec![root_node_id];
         let root_layer_id = self.layer_of(root_node_id)?;
@@ -41,29 +151,39 @@ impl SceneGraph {
         // and adding their children to the next layer to visit.
         while !nodes_to_visit.is_empty() {
             let mut layer = Layer::new();
+            layer.next_edge_seq = cur_layer.next_edge_seq;
+            layer.kind = cur_layer.kind;
             let mut next_nodes_to_visit = Vec::new();
             for nid in nodes_to_visit {
                 if let Ok(node) = cur_layer.node(nid) {
                     next_nodes_to_visit.extend(node.children.iter());
-                    layer.push_node(node.clone());
+                    layer.push_node(node.clone())?;
                 }
             }
+            layer.sparse_edges = cur_layer.sparse_edges.as_ref().map(|edges| {
+                layer
+                    .nodes
+                    .iter()
+                    .filter_map(|n| Some((n.id, edges.get(&n.id)?.clone())))
+                    .collect()
+            });
             // Prune edges to only include those between nodes in the subgraph
             layer.prune();
             layers.push(layer);
-            if root_layer_id < layers.len() {
-                break; // Reached the bottom layer
+            if layers.len() > max_depth {
+                break; // Reached the depth limit
             }
-            cur_layer = match self.layer(root_layer_id - layers.len()) {
+            let Some(next_layer_id) = root_layer_id.checked_sub(layers.len()) else {
+                break; // Reached the bottom layer
+            };
+            cur_layer = match self.layer(next_layer_id) {
                 Ok(l) => l,
                 Err(_) => break, // No more layers to process
             };
             nodes_to_visit = next_nodes_to_visit;
         }
         // Ensure the subgraph has the same number of layers as the original up to the root layer
-        if root_layer_id > layers.len() {
-            layers.extend(std::iter::repeat_with(Layer::new).take(root_layer_id - layers.len()));
-        }
+        layers.resize_with(root_layer_id + 1, Layer::new);
 
         // remove the parent id of the root node.
         // root node and first layer do exist in the subgraph hence the unwraps.
@@ -76,125 +196,1001 @@ impl SceneGraph {
 
         Ok(Self {
             node_counter: self.node_counter,
+            derived_at: Some(self.node_counter),
             layers: layers.into_iter().rev().collect(),
+            cross_edges: Vec::new(),
+            generation: 0,
+            index: NodeIndex::default(),
         })
     }
+
+    /// Create a scene graph holding only the top `n` layers of this one, e.g. to get a
+    /// lightweight semantic view without the coordinate layer.
+    /// Children of the nodes on the new bottom layer are cleared, as are parent links and cross
+    /// edges to nodes of the dropped layers. If `n` exceeds the number of layers, all layers are kept.
+    pub fn top_layers(&self, n: usize) -> Self
+    where
+        T: Clone,
+    {
+        let dropped = self.layers.len().saturating_sub(n);
+        let dropped_ids = self.layers[..dropped]
+            .iter()
+            .flat_map(|l| l.nodes.iter().map(|n| n.id))
+            .collect::<HashSet<_>>();
+        let mut layers = self.layers[dropped..].to_vec();
+        if let Some(bottom) = layers.first_mut() {
+            bottom.nodes.iter_mut().for_each(|n| n.children.clear());
+        }
+        for node in layers.iter_mut().flat_map(|l| l.nodes.iter_mut()) {
+            if node.pid.is_some_and(|pid| dropped_ids.contains(&pid)) {
+                node.pid = None;
+            }
+        }
+        let cross_edges = self
+            .cross_edges
+            .iter()
+            .filter(|(src, dst, _)| !dropped_ids.contains(src) && !dropped_ids.contains(dst))
+            .cloned()
+            .collect();
+        Self {
+            layers,
+            node_counter: self.node_counter,
+            derived_at: Some(self.node_counter),
+            cross_edges,
+            generation: 0,
+            index: NodeIndex::default(),
+        }
+    }
 }
 
 /// SceneGraph Update
-impl SceneGraph {
+impl<T> SceneGraph<T> {
     /// Merge another SceneGraph into this one.
     /// This Process will not delete any nodes or edges, but will apply any change in nodes
     /// features and/or edges between two nodes that exist in both SceneGraphs.
-    pub fn merge(&mut self, m: SceneGraph) -> Result<()> {
-        for mergee_node in m.layers.iter().flat_map(|l| l.nodes.iter()) {
-            if let Some(pid) = mergee_node.pid {
-                self.nest(mergee_node.id).under(pid)?;
+    ///
+    /// Nodes are matched by ID, so `m` is expected to be derived from this graph (e.g. a
+    /// [`subgraph`](SceneGraph::subgraph) or a clone) or to allocate IDs past this graph's node
+    /// counter. If both graphs created nodes since `m` was derived through
+    /// [`subgraph`](SceneGraph::subgraph), [`visible_subgraph`](SceneGraph::visible_subgraph)
+    /// or [`top_layers`](SceneGraph::top_layers), the nodes created in `m` whose IDs are taken in
+    /// this graph are given new IDs instead of being merged with unrelated nodes. As nodes merged
+    /// back are indistinguishable from such nodes afterwards, a derived graph is meant to be
+    /// merged back once. Clones are not derived graphs in that sense: nodes created in a clone
+    /// are merged with the nodes of the same ID in this graph.
+    /// After the merge the node counter is past every ID of both graphs.
+    ///
+    /// Returns the new IDs of the nodes of `m` that were given one, by their ID in `m`.
+    pub fn merge(&mut self, m: Self) -> Result<HashMap<usize, usize>> {
+        self.merge_with(m, MergePolicy::default())
+    }
+
+    /// Merge another SceneGraph into this one, reconciling edges of nodes that exist in both
+    /// SceneGraphs according to the given `policy`.
+    /// With [`MergePolicy::ReplaceEdges`], the edges of every node present in `m` are replaced
+    /// by the ones in `m`, which allows an update to remove stale edges.
+    /// Cross edges of `m` are added like with [`add_cross_edge`](SceneGraph::add_cross_edge),
+    /// except that the ones which would close a cycle with the cross edges of this graph are
+    /// left out rather than failing the merge.
+    /// Returns the new IDs of the nodes of `m` that were given one, like
+    /// [`merge`](SceneGraph::merge), or an error without changing anything if a layer to merge
+    /// into is frozen.
+    pub fn merge_with(
+        &mut self,
+        mut m: Self,
+        policy: MergePolicy,
+    ) -> Result<HashMap<usize, usize>> {
+        for layer in self.layers.iter().take(m.layers.len()) {
+            layer.ensure_unfrozen()?;
+        }
+
+        // nodes created in `m` since it was derived clash with the nodes this graph created
+        // in the meantime if they got the same IDs, so they are moved past both node counters
+        let mut remapped = HashMap::new();
+        if let Some(derived_at) = m.derived_at {
+            let mut next_id = self.node_counter.max(m.node_counter);
+            let clashing = m
+                .layers
+                .iter()
+                .flat_map(|l| l.nodes.iter().map(|n| n.id))
+                .filter(|&id| id >= derived_at && self.locate(id).is_some())
+                .map(|id| {
+                    next_id += 1;
+                    (id, next_id - 1)
+                })
+                .collect::<HashMap<_, _>>();
+            if !clashing.is_empty() {
+                m.remap_ids(|id| clashing.get(&id).copied().unwrap_or(id));
+                m.node_counter = next_id;
             }
+            remapped = clashing;
         }
+
+        // nesting is applied once all nodes of `m` exist in this graph
+        let nestings = m
+            .layers
+            .iter()
+            .flat_map(|l| l.nodes.iter())
+            .filter_map(|n| n.pid.map(|pid| (n.id, pid)))
+            .collect::<Vec<_>>();
+        self.node_counter = self.node_counter.max(m.node_counter);
+        self.touch();
+
+        let mut mergee_layers = m.layers.into_iter();
         self.layers
             .iter_mut()
-            .zip(m.layers)
-            .try_for_each(|(l1, l2)| l1.merge(l2))
+            .zip(mergee_layers.by_ref())
+            .try_for_each(|(l1, l2)| l1.merge_with(l2, policy).map(|_| ()))?;
+        self.layers.extend(mergee_layers);
+
+        for (nid, pid) in nestings {
+            self.nest(nid).under(pid)?;
+        }
+
+        // cross edges are validated like in `add_cross_edge` once all nodes of `m` exist in this
+        // graph, and the ones that would close a cycle are left out
+        for (src, dst, desc) in m.cross_edges {
+            if self
+                .cross_edges
+                .iter()
+                .any(|(s, d, _)| *s == src && *d == dst)
+            {
+                continue;
+            }
+            match self.add_cross_edge(src, dst, &desc) {
+                Ok(()) | Err(AtlasError::CyclicCrossEdge(..) | AtlasError::NodeNotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(remapped)
+    }
+
+    /// Append the layers of another SceneGraph to the matching layers of this one, side by side.
+    /// Unlike [`merge`](SceneGraph::merge), nodes are never matched: the IDs of `other` are
+    /// offset past this graph's node counter, keeping its edges, cross edges and hierarchy
+    /// intact. Layers `other` has beyond this graph's top layer are added on top.
+    /// Returns an error without changing anything if a layer to append to is frozen.
+    pub fn union_layers(&mut self, mut other: Self) -> Result<()> {
+        for layer in self.layers.iter().take(other.layers.len()) {
+            layer.ensure_unfrozen()?;
+        }
+
+        let offset = self.node_counter;
+        other.remap_ids(|id| id + offset);
+        for (lid, mut layer) in other.layers.into_iter().enumerate() {
+            match self.layers.get_mut(lid) {
+                Some(existing) => {
+                    existing.next_edge_seq = existing.next_edge_seq.max(layer.next_edge_seq);
+                    existing.kind = existing.kind.or(layer.kind);
+                    layer.set_edge_storage(EdgeStorage::PerNode);
+                    existing.nodes.append(&mut layer.nodes);
+                    // move the appended edges to the storage of the existing layer
+                    existing.set_edge_storage(existing.edge_storage());
+                }
+                None => self.layers.push(layer),
+            }
+        }
+        self.cross_edges.extend(other.cross_edges);
+        self.node_counter += other.node_counter;
+        self.touch();
+        Ok(())
+    }
+
+    /// Replace every node ID with `map(id)`, wherever it is referenced.
+    /// The node counter is left as it is.
+    fn remap_ids(&mut self, map: impl Fn(usize) -> usize) {
+        let remap = |id: &mut usize| *id = map(*id);
+        for layer in &mut self.layers {
+            let storage = layer.edge_storage();
+            layer.set_edge_storage(EdgeStorage::PerNode);
+            for node in layer.nodes.iter_mut() {
+                remap(&mut node.id);
+                node.pid.iter_mut().for_each(remap);
+                node.children.iter_mut().for_each(remap);
+                for edge in node.edges.iter_mut() {
+                    remap(&mut edge.src);
+                    remap(&mut edge.dst);
+                }
+            }
+            layer.set_edge_storage(storage);
+        }
+        for (src, dst, _) in self.cross_edges.iter_mut() {
+            remap(src);
+            remap(dst);
+        }
+        self.touch();
+        self.index.invalidate();
+    }
+
+    /// Merge a layer into the top layer of this SceneGraph.
+    /// Nodes are merged as in [`merge`](SceneGraph::merge), but parent/child links are not
+    /// considered at all, which makes it a cheap path for updates that only touch the top layer.
+    /// The node counter is moved past the IDs of added nodes.
+    pub fn merge_top_layer(&mut self, other_top: Layer<T>) -> Result<MergeReport> {
+        let report = self
+            .top_layer_mut()?
+            .merge_with(other_top, MergePolicy::default())?;
+        if let Some(max_id) = report.added.iter().max() {
+            self.node_counter = self.node_counter.max(max_id + 1);
+        }
+        Ok(report)
+    }
+}
+
+/// Serialization
+impl<T> SceneGraph<T> {
+    /// Save the scene graph to a file.
+    /// The format is chosen by the file extension: `.json` for JSON and `.bin` for the binary
+    /// format of [`to_bytes`](SceneGraph::to_bytes), which requires the `bincode` feature.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()>
+    where
+        T: Serialize,
+    {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.write_json(BufWriter::new(File::create(path)?)),
+            #[cfg(feature = "bincode")]
+            Some("bin") => Ok(std::fs::write(path, self.to_bytes()?)?),
+            ext => Err(AtlasError::UnsupportedFormat(
+                ext.unwrap_or_default().to_string(),
+            )),
+        }
+    }
+
+    /// Serialize the scene graph into a JSON string.
+    pub fn to_json(&self) -> Result<String>
+    where
+        T: Serialize,
+    {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Deserialize a scene graph from a JSON string produced by
+    /// [`to_json`](SceneGraph::to_json) or [`write_json`](SceneGraph::write_json).
+    pub fn from_json(s: &str) -> Result<Self>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    /// Write the scene graph as JSON into `writer`.
+    /// The graph is streamed layer by layer and node by node, without building the whole
+    /// document in memory first, so large graphs can be exported with bounded memory.
+    pub fn write_json(&self, writer: impl Write) -> Result<()>
+    where
+        T: Serialize,
+    {
+        let mut serializer = serde_json::Serializer::new(writer);
+        self.serialize(&mut serializer)?;
+        serializer.into_inner().flush()?;
+        Ok(())
+    }
+
+    /// Write the [`visible_subgraph`](SceneGraph::visible_subgraph) under the specified root
+    /// node as JSON into `writer`, e.g. to send a thin client only what the observer sees.
+    /// Only the visible part of the graph is cloned before being streamed like
+    /// [`write_json`](SceneGraph::write_json).
+    pub fn serialize_visible(
+        &self,
+        observer: Observer,
+        root_node_id: usize,
+        writer: impl Write,
+    ) -> Result<()>
+    where
+        T: Clone + Serialize,
+    {
+        self.visible_subgraph(observer, root_node_id)?
+            .write_json(writer)
+    }
+
+    /// Load a scene graph from a file saved by [`save`](SceneGraph::save).
+    /// The format is chosen by the file extension, see [`save`](SceneGraph::save).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => {
+                let reader = BufReader::new(File::open(path)?);
+                Ok(serde_json::from_reader(reader)?)
+            }
+            #[cfg(feature = "bincode")]
+            Some("bin") => Self::from_bytes(&std::fs::read(path)?),
+            ext => Err(AtlasError::UnsupportedFormat(
+                ext.unwrap_or_default().to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl<T> SceneGraph<T> {
+    /// Serialize the scene graph into a compact binary representation.
+    pub fn to_bytes(&self) -> Result<Vec<u8>>
+    where
+        T: Serialize,
+    {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Deserialize a scene graph from its binary representation produced by
+    /// [`to_bytes`](SceneGraph::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        Ok(bincode::deserialize(bytes)?)
     }
 }
 
 /// Layer Accessors
-impl SceneGraph {
+impl<T> SceneGraph<T> {
     /// Get a mutable reference to the top layer.
-    pub fn top_layer_mut(&mut self) -> Result<&mut Layer> {
+    pub fn top_layer_mut(&mut self) -> Result<&mut Layer<T>> {
+        self.touch();
         self.layers
             .last_mut()
             .ok_or(AtlasError::LayerOutOfBounds(0, 0))
     }
 
     /// Get an immutable reference to the top layer.
-    pub fn top_layer(&self) -> Result<&Layer> {
+    pub fn top_layer(&self) -> Result<&Layer<T>> {
         self.layers.last().ok_or(AtlasError::LayerOutOfBounds(0, 0))
     }
 
     /// Get an immutable reference to a layer by its index.
-    pub fn layer(&self, index: usize) -> Result<&Layer> {
+    pub fn layer(&self, index: usize) -> Result<&Layer<T>> {
         self.layers
             .get(index)
             .ok_or(AtlasError::LayerOutOfBounds(index, self.layers.len()))
     }
 
     /// Get a mutable reference to a layer by its index.
-    pub fn layer_mut(&mut self, index: usize) -> Result<&mut Layer> {
+    pub fn layer_mut(&mut self, index: usize) -> Result<&mut Layer<T>> {
+        self.touch();
         let layers_count = self.layers.len();
         self.layers
             .get_mut(index)
             .ok_or(AtlasError::LayerOutOfBounds(index, layers_count))
     }
 
-    /// Get the layer index of a node by its ID.
-    pub fn layer_of(&self, nid: usize) -> Result<usize, AtlasError> {
-        let nestee_layer_id = self
+    /// Remove the layers holding no nodes, returning how many were removed.
+    /// An empty layer is kept if removing it would break the hierarchy, i.e. if nodes on the
+    /// layer below have a parent or nodes on the layer above have children, as those links
+    /// would point to the wrong layer afterwards. Frozen layers are kept as well.
+    pub fn remove_empty_layers(&mut self) -> usize {
+        let mut removed = 0;
+        for lid in (0..self.layers.len()).rev() {
+            let layer = &self.layers[lid];
+            if !layer.is_empty() || layer.frozen {
+                continue;
+            }
+            let linked_below = lid
+                .checked_sub(1)
+                .is_some_and(|below| self.layers[below].nodes.iter().any(|n| n.pid.is_some()));
+            let linked_above = self
+                .layers
+                .get(lid + 1)
+                .is_some_and(|above| above.nodes.iter().any(|n| !n.children.is_empty()));
+            if !linked_below && !linked_above {
+                self.layers.remove(lid);
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            self.touch();
+        }
+        removed
+    }
+
+    /// Remove duplicate edges from every node, keeping one edge per `(src, dst, desc)`, see
+    /// [`Node::dedup_edges`]. Frozen layers are left untouched.
+    /// Returns the number of removed edges.
+    pub fn dedup_all_edges(&mut self) -> usize {
+        let removed = self
             .layers
+            .iter_mut()
+            .filter(|l| !l.frozen)
+            .flat_map(|l| l.nodes.iter_mut())
+            .map(|n| n.dedup_edges())
+            .sum();
+        if removed > 0 {
+            self.touch();
+        }
+        removed
+    }
+
+    /// Get a copy of a layer, to be [restored](SceneGraph::restore_layer) later, e.g. to undo
+    /// local edits without cloning the whole scene graph.
+    pub fn snapshot_layer(&self, index: usize) -> Result<Layer<T>>
+    where
+        T: Clone,
+    {
+        self.layer(index).cloned()
+    }
+
+    /// Replace a layer with a [snapshot](SceneGraph::snapshot_layer) of it.
+    /// Parent/child links and cross edges of the other layers are left as they are, so if nodes
+    /// were added to or removed from the layer since the snapshot, they may no longer be
+    /// reciprocal; use [`repair_hierarchy`](SceneGraph::repair_hierarchy) to fix them.
+    /// Returns an error if the current layer is frozen.
+    pub fn restore_layer(&mut self, index: usize, layer: Layer<T>) -> Result<()> {
+        let current = self.layer_mut(index)?;
+        current.ensure_unfrozen()?;
+        *current = layer;
+        self.index.invalidate();
+        Ok(())
+    }
+
+    /// Get the first layer [tagged](Layer::set_kind) as [`Metric`](LayerKind::Metric).
+    pub fn metric_layer(&self) -> Result<&Layer<T>> {
+        self.layer_of_kind(LayerKind::Metric)
+    }
+
+    /// Get the first layer [tagged](Layer::set_kind) as [`Semantic`](LayerKind::Semantic).
+    pub fn semantic_layer(&self) -> Result<&Layer<T>> {
+        self.layer_of_kind(LayerKind::Semantic)
+    }
+
+    /// Get the first layer tagged with the specified kind.
+    fn layer_of_kind(&self, kind: LayerKind) -> Result<&Layer<T>> {
+        Ok(&self.layers[self.layer_index_of_kind(kind)?])
+    }
+
+    /// Get the index of the first layer tagged with the specified kind.
+    fn layer_index_of_kind(&self, kind: LayerKind) -> Result<usize> {
+        self.layers
             .iter()
-            .position(|l| l.node(nid).is_ok())
-            .ok_or(AtlasError::NodeNotFound)?;
-        Ok(nestee_layer_id)
+            .position(|l| l.kind == Some(kind))
+            .ok_or(AtlasError::LayerKindNotFound(kind))
+    }
+
+    /// Freeze a layer, making it read-only: pushing nodes, adding or deleting edges and
+    /// deleting nodes on it will fail until it is unfrozen.
+    pub fn freeze_layer(&mut self, index: usize) -> Result<()> {
+        self.layer_mut(index)?.frozen = true;
+        Ok(())
+    }
+
+    /// Unfreeze a layer previously frozen with [`freeze_layer`](SceneGraph::freeze_layer).
+    pub fn unfreeze_layer(&mut self, index: usize) -> Result<()> {
+        self.layer_mut(index)?.frozen = false;
+        Ok(())
+    }
+
+    /// Take ownership of all nodes of a layer by its index, leaving the layer empty.
+    /// Parent/child links of nodes on the adjacent layers are not updated and are left dangling.
+    pub fn take_layer_nodes(&mut self, index: usize) -> Result<Vec<Node<T>>> {
+        self.layer_mut(index).map(Layer::take_nodes)
+    }
+
+    /// Get the number of layers in the scene graph.
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Check whether the scene graph holds no nodes, i.e. it has no layers or only empty ones.
+    pub fn is_empty(&self) -> bool {
+        self.layers.iter().all(Layer::is_empty)
+    }
+
+    /// Get the index of the layer directly above the layer at `index`.
+    /// Returns an error if `index` is the top layer.
+    pub fn layer_above(&self, index: usize) -> Result<usize> {
+        index
+            .checked_add(1)
+            .filter(|&above| above < self.layers.len())
+            .ok_or(AtlasError::LayerOutOfBounds(
+                index.saturating_add(1),
+                self.layers.len(),
+            ))
+    }
+
+    /// Get the index of the layer directly below the layer at `index`.
+    /// Returns an error if `index` is the bottom layer.
+    pub fn layer_below(&self, index: usize) -> Result<usize> {
+        index.checked_sub(1).ok_or(AtlasError::NoLayerBelow(index))
+    }
+
+    /// Get the layer index of a node by its ID.
+    pub fn layer_of(&self, nid: usize) -> Result<usize, AtlasError> {
+        let (lid, _) = self.locate(nid).ok_or(AtlasError::NodeNotFound)?;
+        Ok(lid)
+    }
+
+    /// Get the `(layer, position)` of a node in `layers`.
+    fn locate(&self, nid: usize) -> Option<(usize, usize)> {
+        self.index.locate(self.generation, &self.layers, nid)
     }
 }
 
 /// Node Accessors
-impl SceneGraph {
+impl<T> SceneGraph<T> {
     /// Get an immutable reference to a node by its ID.
-    pub fn node(&self, nid: usize) -> Result<&Node> {
-        self.layers
-            .iter()
-            .find_map(|layer| layer.node(nid).ok())
-            .ok_or(AtlasError::NodeNotFound)
+    pub fn node(&self, nid: usize) -> Result<&Node<T>> {
+        let (lid, pos) = self.locate(nid).ok_or(AtlasError::NodeNotFound)?;
+        Ok(&self.layers[lid].nodes[pos])
     }
 
     /// Get a mutable reference to a node by its ID.
-    pub fn node_mut(&mut self, nid: usize) -> Result<&mut Node> {
+    pub fn node_mut(&mut self, nid: usize) -> Result<&mut Node<T>> {
+        let (lid, pos) = self.locate(nid).ok_or(AtlasError::NodeNotFound)?;
+        self.touch();
+        Ok(&mut self.layers[lid].nodes[pos])
+    }
+
+    /// Apply a function to every node of every layer, e.g. for bulk feature normalization.
+    pub fn for_each_node_mut(&mut self, f: impl FnMut(&mut Node<T>)) {
+        self.touch();
+        self.index.invalidate();
         self.layers
             .iter_mut()
-            .find_map(|layer| layer.node_mut(nid).ok())
-            .ok_or(AtlasError::NodeNotFound)
+            .flat_map(|l| l.nodes.iter_mut())
+            .for_each(f);
+    }
+
+    /// Get an immutable reference to the user data of a node by its ID.
+    pub fn node_data(&self, nid: usize) -> Result<&T> {
+        self.node(nid).map(|n| &n.data)
+    }
+
+    /// Get a mutable reference to the user data of a node by its ID.
+    pub fn node_data_mut(&mut self, nid: usize) -> Result<&mut T> {
+        self.node_mut(nid).map(|n| &mut n.data)
     }
 }
 
 /// Node Manipulation
-impl SceneGraph {
+impl<T> SceneGraph<T> {
     /// Create a new Metric Node with specified coordinates and features.
-    pub fn new_coordinates(&mut self, x: f32, y: f32, z: f32, features: Vec<Feature>) -> Node {
-        let node = Node::new(self.node_counter, features, Some(Coordinate::new(x, y, z)));
+    pub fn new_coordinates(&mut self, x: f32, y: f32, z: f32, features: Vec<Feature>) -> Node<T>
+    where
+        T: Default,
+    {
+        let coordinates = Some(Coordinate::new(x, y, z));
+        let node = Node::with_data(self.node_counter, features, coordinates, T::default());
         self.node_counter += 1;
         node
     }
 
+    /// Add an object to a graph shaped like [`new_metric_semantic`](SceneGraph::new_metric_semantic):
+    /// a semantic node with the specified features, with a coordinate node nested under it for
+    /// every `(position, color)` point of the object.
+    /// Returns the ID of the semantic node.
+    pub fn add_object(
+        &mut self,
+        features: Vec<Feature>,
+        points: &[([f32; 3], [f32; 3])],
+    ) -> Result<usize>
+    where
+        T: Default,
+    {
+        let metric = self.layer_index_of_kind(LayerKind::Metric)?;
+        let semantic = self.layer_index_of_kind(LayerKind::Semantic)?;
+        if semantic != metric + 1 {
+            return Err(AtlasError::InvalidLayersForNesting(metric, semantic));
+        }
+        self.layers[metric].ensure_unfrozen()?;
+        self.layers[semantic].ensure_unfrozen()?;
+
+        // the nodes are new, so they are linked up front instead of being nested one by one
+        let mut object = self.new_node(features);
+        let object_id = object.id;
+        let mut nodes = Vec::with_capacity(points.len());
+        for &(position, color) in points {
+            let [x, y, z] = position;
+            let mut node = self.new_coordinates(x, y, z, Vec::new());
+            node.points
+                .push(Point::new(Coordinate::from(position), color));
+            node.pid = Some(object_id);
+            object.children.push(node.id);
+            nodes.push(node);
+        }
+        self.layer_mut(semantic)?.push_node(object)?;
+        let metric_layer = self.layer_mut(metric)?;
+        metric_layer.nodes.reserve(nodes.len());
+        for node in nodes {
+            metric_layer.push_node(node)?;
+        }
+        Ok(object_id)
+    }
+
     /// Create a new Semantic Node with specified features.
-    pub fn new_node(&mut self, features: Vec<Feature>) -> Node {
-        let node = Node::new(self.node_counter, features, None);
+    pub fn new_node(&mut self, features: Vec<Feature>) -> Node<T>
+    where
+        T: Default,
+    {
+        self.new_node_with(features, T::default())
+    }
+
+    /// Create a new Semantic Node with specified features carrying the given user data.
+    pub fn new_node_with(&mut self, features: Vec<Feature>, data: T) -> Node<T> {
+        let node = Node::with_data(self.node_counter, features, None, data);
         self.node_counter += 1;
         node
     }
 
+    /// Create a new Semantic Node with specified features and push it to a layer in one step.
+    /// The layer is checked before an ID is allocated, so a missing or frozen layer leaves the
+    /// node counter unchanged. Returns the ID of the new node.
+    pub fn spawn_node(&mut self, layer: usize, features: Vec<Feature>) -> Result<usize>
+    where
+        T: Default,
+    {
+        self.layer(layer)?.ensure_unfrozen()?;
+        let node = self.new_node(features);
+        let nid = node.id;
+        self.layer_mut(layer)?.push_node(node)?;
+        Ok(nid)
+    }
+
     /// Delete a node by its ID from the Scene Graph.
     /// This will also recursively delete all child nodes of the specified node.
     /// If the node has a parent, it will be removed from the parent's list of children.
+    /// Returns an error without deleting anything if any affected layer is frozen.
     pub fn del_node(&mut self, nid: usize) -> Result<()> {
-        // Remove node from its parent's children list
         let lid = self.layer_of(nid)?;
+        let max_depth = self.bfs(nid).map(|(depth, _)| depth).max().unwrap_or(0);
+        for affected in lid.saturating_sub(max_depth)..=lid {
+            self.layer(affected)?.ensure_unfrozen()?;
+        }
+
+        // Remove node from its parent's children list
         let layer = self.layer_mut(lid)?;
         if let Some(pid) = layer.node(nid)?.pid {
-            self.layer_mut(lid + 1)?.node_mut(pid)?.remove_child(nid)?;
+            let parent_lid = self.layer_above(lid)?;
+            self.layer_mut(parent_lid)?
+                .node_mut(pid)?
+                .remove_child(nid)?;
         }
 
         // Recursively delete the node and its children
-        fn del_node_recursive(sg: &mut SceneGraph, lid: usize, nid: usize) -> Result<()> {
+        fn del_node_recursive<T>(sg: &mut SceneGraph<T>, lid: usize, nid: usize) -> Result<()> {
             let layer = sg.layer_mut(lid)?;
             let children = layer.del_node(nid)?.children;
-            for child_id in children {
-                del_node_recursive(sg, lid - 1, child_id)?;
+            if !children.is_empty() {
+                let child_lid = sg.layer_below(lid)?;
+                for child_id in children {
+                    del_node_recursive(sg, child_lid, child_id)?;
+                }
             }
             Ok(())
         }
-        del_node_recursive(self, lid, nid)
+        del_node_recursive(self, lid, nid)?;
+
+        // Drop cross edges pointing from or to the deleted nodes
+        let cross_edges = std::mem::take(&mut self.cross_edges);
+        self.cross_edges = cross_edges
+            .into_iter()
+            .filter(|(src, dst, _)| self.node(*src).is_ok() && self.node(*dst).is_ok())
+            .collect();
+        Ok(())
+    }
+
+    /// Renumber all nodes to the dense `0..n` range, keeping their relative order, and reset the
+    /// node counter to `n`. Parent/child links, edges and cross edges are rewritten accordingly,
+    /// and references to nodes that do not exist are dropped.
+    /// Returns the mapping from old to new IDs.
+    pub fn compact_ids(&mut self) -> HashMap<usize, usize> {
+        let mut old_ids = self
+            .layers
+            .iter()
+            .flat_map(|l| l.nodes.iter().map(|n| n.id))
+            .collect::<Vec<_>>();
+        old_ids.sort_unstable();
+        let mapping = old_ids
+            .into_iter()
+            .enumerate()
+            .map(|(new, old)| (old, new))
+            .collect::<HashMap<_, _>>();
+
+        let storages = self
+            .layers
+            .iter_mut()
+            .map(|l| {
+                let storage = l.edge_storage();
+                l.set_edge_storage(EdgeStorage::PerNode);
+                storage
+            })
+            .collect::<Vec<_>>();
+        for node in self.layers.iter_mut().flat_map(|l| l.nodes.iter_mut()) {
+            node.id = mapping[&node.id];
+            node.pid = node.pid.and_then(|pid| mapping.get(&pid).copied());
+            node.children = node
+                .children
+                .iter()
+                .filter_map(|cid| mapping.get(cid).copied())
+                .collect();
+            node.edges.retain(|e| mapping.contains_key(&e.dst));
+            for edge in node.edges.iter_mut() {
+                edge.src = node.id;
+                edge.dst = mapping[&edge.dst];
+            }
+        }
+        self.cross_edges = std::mem::take(&mut self.cross_edges)
+            .into_iter()
+            .filter_map(|(src, dst, desc)| Some((*mapping.get(&src)?, *mapping.get(&dst)?, desc)))
+            .collect();
+        for (layer, storage) in self.layers.iter_mut().zip(storages) {
+            layer.set_edge_storage(storage);
+        }
+
+        self.node_counter = mapping.len();
+        self.touch();
+        self.index.invalidate();
+        mapping
+    }
+
+    /// Add a directed cross edge from `src` to `dst`, which may be on any layers.
+    /// Unlike intra-layer edges, cross edges can link any two nodes of the scene graph, e.g. to
+    /// express "part of" or "instance of" relations beyond the parent/child hierarchy.
+    /// Both nodes must exist and the edge must not close a cycle among the cross edges.
+    pub fn add_cross_edge(&mut self, src: usize, dst: usize, desc: &str) -> Result<()> {
+        self.node(src)?;
+        self.node(dst)?;
+
+        // the edge closes a cycle if `src` is reachable from `dst`
+        let mut visited = HashSet::new();
+        let mut stack = vec![dst];
+        while let Some(nid) = stack.pop() {
+            if nid == src {
+                return Err(AtlasError::CyclicCrossEdge(src, dst));
+            }
+            if visited.insert(nid) {
+                stack.extend(self.cross_edges_from(nid).into_iter().map(|(_, d, _)| *d));
+            }
+        }
+
+        self.touch();
+        self.cross_edges.push((src, dst, desc.to_string()));
+        Ok(())
+    }
+
+    /// Snap the coordinates of every node on a layer to the nearest multiple of `grid`, e.g. to
+    /// collapse near-identical positions from noisy imports. Returns how many coordinates changed.
+    /// A `grid` that is not both finite and positive, e.g. NaN, leaves the layer unchanged.
+    /// Returns an error if the layer is frozen.
+    pub fn quantize_coordinates(&mut self, layer: usize, grid: f32) -> Result<usize> {
+        let layer = self.layer_mut(layer)?;
+        layer.ensure_unfrozen()?;
+        if !(grid.is_finite() && grid > 0.0) {
+            return Ok(0);
+        }
+        let mut changed = 0;
+        for coordinates in layer
+            .nodes
+            .iter_mut()
+            .filter_map(|n| n.coordinates.as_mut())
+        {
+            let snapped = (*coordinates / grid).round() * grid;
+            if snapped != *coordinates {
+                *coordinates = snapped;
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Set the coordinates of many nodes at once, given as `(id, coordinates)` pairs.
+    /// The update is atomic: if any of the nodes does not exist or lives on a frozen layer,
+    /// nothing is changed.
+    pub fn set_coordinates(&mut self, updates: &[(usize, Coordinate)]) -> Result<()> {
+        for &(nid, _) in updates {
+            self.layers[self.layer_of(nid)?].ensure_unfrozen()?;
+        }
+        for &(nid, coordinates) in updates {
+            self.node_mut(nid)?.coordinates = Some(coordinates);
+        }
+        Ok(())
+    }
+
+    /// Set features on many nodes at once, e.g. the output of an external classifier, replacing
+    /// the values of existing features with the same keys. Returns the number of updated nodes.
+    /// The update is atomic: if any of the nodes does not exist or lives on a frozen layer,
+    /// nothing is changed. See [`apply_known_features`](SceneGraph::apply_known_features) to
+    /// skip unknown nodes instead.
+    pub fn apply_features(&mut self, updates: HashMap<usize, Vec<Feature>>) -> Result<usize> {
+        for &nid in updates.keys() {
+            self.layers[self.layer_of(nid)?].ensure_unfrozen()?;
+        }
+        self.apply_known_features(updates)
+    }
+
+    /// Set features on many nodes at once like [`apply_features`](SceneGraph::apply_features),
+    /// skipping the nodes that do not exist. Returns the number of updated nodes.
+    pub fn apply_known_features(&mut self, updates: HashMap<usize, Vec<Feature>>) -> Result<usize> {
+        let updates = updates
+            .into_iter()
+            .filter_map(|(nid, features)| Some((self.layer_of(nid).ok()?, nid, features)))
+            .collect::<Vec<_>>();
+        for (lid, _, _) in &updates {
+            self.layers[*lid].ensure_unfrozen()?;
+        }
+        for (_, nid, features) in &updates {
+            let node = self.node_mut(*nid)?;
+            features.iter().for_each(|f| node.set_feature(f.clone()));
+        }
+        Ok(updates.len())
+    }
+
+    /// Paint the point clouds of the nodes having the feature `key` set to `value` with
+    /// `color`. Returns the number of recolored nodes, i.e. matching nodes with a point cloud.
+    /// Fails without changing anything if a matching node lives on a frozen layer.
+    pub fn colorize_by_feature(
+        &mut self,
+        key: &str,
+        value: &str,
+        color: [f32; 3],
+    ) -> Result<usize> {
+        let feature = Feature::new(key, value);
+        for layer in &self.layers {
+            if layer.nodes.iter().any(|n| n.match_feature(&feature)) {
+                layer.ensure_unfrozen()?;
+            }
+        }
+        let mut recolored = 0;
+        for node in self
+            .layers
+            .iter_mut()
+            .flat_map(|l| l.nodes.iter_mut())
+            .filter(|n| n.match_feature(&feature) && !n.points.is_empty())
+        {
+            node.points.iter_mut().for_each(|p| p.color = color);
+            recolored += 1;
+        }
+        if recolored > 0 {
+            self.touch();
+        }
+        Ok(recolored)
+    }
+
+    /// Change the kind of a node.
+    /// Switching to [`Semantic`](NodeKind::Semantic) clears the coordinates and point cloud of
+    /// the node. Switching to [`Metric`](NodeKind::Metric) positions the node at its
+    /// [representative point](Node::representative_point), and fails if it has none.
+    pub fn set_node_kind(&mut self, nid: usize, kind: NodeKind) -> Result<()> {
+        self.layers[self.layer_of(nid)?].ensure_unfrozen()?;
+        let node = self.node_mut(nid)?;
+        match kind {
+            NodeKind::Semantic => {
+                node.coordinates = None;
+                node.points.clear();
+            }
+            NodeKind::Metric => {
+                let point = node
+                    .representative_point()
+                    .ok_or(AtlasError::CoordinatesRequired)?;
+                node.coordinates = Some(point);
+            }
+        }
+        Ok(())
+    }
+
+    /// Split a node by a plane, moving the points of its point cloud on the negative side of the
+    /// plane (opposite to `plane_normal`) into a new sibling node with the same parent and a copy
+    /// of its features. The coordinates of both nodes are set to the
+    /// [representative point](Node::representative_point) of their half of the point cloud.
+    /// Returns the ID of the new node.
+    /// Returns [`PointNotFound`](AtlasError::PointNotFound) without changing anything if the
+    /// node has no point cloud or if all of its points lie on the same side of the plane.
+    pub fn split_node(
+        &mut self,
+        nid: usize,
+        plane_point: Coordinate,
+        plane_normal: Coordinate,
+    ) -> Result<usize>
+    where
+        T: Default,
+    {
+        let lid = self.layer_of(nid)?;
+        let node = self.node(nid)?;
+        if node.points.is_empty() {
+            return Err(AtlasError::PointNotFound);
+        }
+        let pid = node.pid;
+        let (negative, positive) = node
+            .points
+            .iter()
+            .partition::<Vec<_>, _>(|p| (p.position - plane_point).dot(plane_normal) < 0.0);
+        if negative.is_empty() || positive.is_empty() {
+            return Err(AtlasError::PointNotFound);
+        }
+
+        let mut sibling = self.new_node(node.features.clone());
+        let sibling_id = sibling.id;
+        sibling.points = negative;
+        sibling.coordinates = sibling.representative_point();
+        self.layer_mut(lid)?.push_node(sibling)?;
+        let node = self.node_mut(nid)?;
+        node.points = positive;
+        node.coordinates = node.representative_point();
+        if let Some(pid) = pid {
+            self.nest(sibling_id).under(pid)?;
+        }
+        Ok(sibling_id)
+    }
+
+    /// Make parent/child links reciprocal, e.g. after the hierarchy was built by direct field
+    /// mutation. Every node with a parent on the layer above is added to its parent's children,
+    /// parent links to nodes not on the layer above are cleared, and children entries pointing
+    /// to nonexistent nodes or to nodes with another parent are dropped.
+    /// Returns the number of fixes applied.
+    pub fn repair_hierarchy(&mut self) -> usize {
+        let mut fixes = 0;
+        // layers are repaired bottom-up, so the parent links of the layer below are already
+        // repaired when checking the children of a layer.
+        for lid in 0..self.layers.len() {
+            let parents_below = match lid.checked_sub(1) {
+                Some(below) => self.layers[below]
+                    .nodes
+                    .iter()
+                    .map(|n| (n.id, n.pid))
+                    .collect::<HashMap<_, _>>(),
+                None => HashMap::new(),
+            };
+            for node in self.layers[lid].nodes.iter_mut() {
+                let nid = node.id;
+                let before = node.children.len();
+                node.children
+                    .retain(|cid| parents_below.get(cid) == Some(&Some(nid)));
+                fixes += before - node.children.len();
+            }
+
+            // lid is in bounds, so the split always succeeds
+            let (layer, rest) = self.layers[lid..].split_first_mut().unwrap();
+            let mut above = rest.first_mut();
+            for node in layer.nodes.iter_mut() {
+                let Some(pid) = node.pid else {
+                    continue;
+                };
+                match above.as_deref_mut().and_then(|l| l.node_mut(pid).ok()) {
+                    Some(parent) if !parent.children.contains(&node.id) => {
+                        parent.children.push(node.id);
+                        fixes += 1;
+                    }
+                    Some(_) => {}
+                    None => {
+                        node.pid = None;
+                        fixes += 1;
+                    }
+                }
+            }
+        }
+        if fixes > 0 {
+            self.touch();
+        }
+        fixes
+    }
+
+    /// Set the coordinates of every node on a layer to the centroid of the coordinates of its
+    /// children on the layer below. Nodes without any positioned children are left unchanged.
+    pub fn recompute_coordinates_from_children(&mut self, layer: usize) -> Result<()> {
+        let child_layer = self.layer(self.layer_below(layer)?)?;
+        let centroids = self
+            .layer(layer)?
+            .nodes
+            .iter()
+            .filter_map(|n| {
+                let coords = n
+                    .children
+                    .iter()
+                    .filter_map(|&cid| child_layer.node(cid).ok()?.coordinates)
+                    .collect::<Vec<_>>();
+                if coords.is_empty() {
+                    return None;
+                }
+                let centroid = coords.iter().sum::<Coordinate>() / coords.len() as f32;
+                Some((n.id, centroid))
+            })
+            .collect::<Vec<_>>();
+
+        let layer = self.layer_mut(layer)?;
+        for (nid, centroid) in centroids {
+            layer.node_mut(nid)?.coordinates = Some(centroid);
+        }
+        Ok(())
     }
 
     /// Nest a node under another node, establishing a parent-child relationship.
@@ -217,50 +1213,450 @@ impl SceneGraph {
     /// // Create a layer and add nodes to it
     /// sg.new_layer();
     /// sg.new_layer();
-    /// sg.layer_mut(0).unwrap().push_node(node1);
-    /// sg.layer_mut(1).unwrap().push_node(node2);
+    /// sg.layer_mut(0).unwrap().push_node(node1).unwrap();
+    /// sg.layer_mut(1).unwrap().push_node(node2).unwrap();
     ///
     /// // Nest node1 under node2
     /// sg.nest(id1).under(id2).unwrap();
     ///
-    /// assert_eq!(sg.node(id2).unwrap().children, vec![id1]);
-    /// assert_eq!(sg.node(id1).unwrap().pid, Some(id2));
+    /// assert_eq!(sg.node(id2).unwrap().children(), [id1]);
+    /// assert_eq!(sg.node(id1).unwrap().pid(), Some(id2));
     /// ```
-    pub fn nest(&mut self, nid: usize) -> NestUnder<'_> {
+    pub fn nest(&mut self, nid: usize) -> NestUnder<'_, T> {
         NestUnder {
             sg: self,
             nestee: nid,
         }
     }
+
+    /// Move a node under a new parent.
+    /// The `new_parent` node must be on the layer immediately above the node, and must not be
+    /// one of its descendants. The node is detached from its old parent, if any.
+    pub fn reparent(&mut self, nid: usize, new_parent: usize) -> Result<()> {
+        let layer_id = self.layer_of(nid)?;
+        let parent_layer_id = self.layer_of(new_parent)?;
+        if layer_id.checked_add(1) != Some(parent_layer_id) {
+            return Err(AtlasError::InvalidLayersForNesting(
+                layer_id,
+                parent_layer_id,
+            ));
+        }
+        if new_parent == nid || self.ancestors(new_parent)?.contains(&nid) {
+            return Err(AtlasError::CyclicNesting(nid, new_parent));
+        }
+        self.nest(nid).under(new_parent)?;
+        Ok(())
+    }
+}
+
+/// Traversal
+impl<T> SceneGraph<T> {
+    /// Iterate over a node and its descendants depth-first, in pre-order: each node is yielded
+    /// before its children, which are visited in order.
+    /// The traversal uses an explicit stack and is lazy, so it can be stopped early.
+    /// Nothing is yielded if the root node does not exist.
+    pub fn dfs(&self, root: usize) -> impl Iterator<Item = &Node<T>> {
+        let mut stack = match self.layer_of(root) {
+            Ok(lid) => vec![(lid, root)],
+            Err(_) => Vec::new(),
+        };
+        std::iter::from_fn(move || {
+            while let Some((lid, nid)) = stack.pop() {
+                // skip dangling child references
+                let Ok(node) = self.layers[lid].node(nid) else {
+                    continue;
+                };
+                if let Some(child_lid) = lid.checked_sub(1) {
+                    stack.extend(node.children.iter().rev().map(|&cid| (child_lid, cid)));
+                }
+                return Some(node);
+            }
+            None
+        })
+    }
+
+    /// Iterate over a node and its descendants breadth-first, yielding `(depth, node)` pairs.
+    /// The root has depth 0 and nodes are yielded level by level, so every node one layer down
+    /// comes after all nodes of the layer above.
+    /// Nothing is yielded if the root node does not exist.
+    pub fn bfs(&self, root: usize) -> impl Iterator<Item = (usize, &Node<T>)> {
+        let mut queue = VecDeque::new();
+        if let Ok(lid) = self.layer_of(root) {
+            queue.push_back((0, lid, root));
+        }
+        std::iter::from_fn(move || {
+            while let Some((depth, lid, nid)) = queue.pop_front() {
+                // skip dangling child references
+                let Ok(node) = self.layers[lid].node(nid) else {
+                    continue;
+                };
+                if let Some(child_lid) = lid.checked_sub(1) {
+                    queue.extend(node.children.iter().map(|&cid| (depth + 1, child_lid, cid)));
+                }
+                return Some((depth, node));
+            }
+            None
+        })
+    }
+
+    /// Get all leaf nodes, i.e. nodes without children, across all layers.
+    pub fn leaves(&self) -> Vec<&Node<T>> {
+        self.layers
+            .iter()
+            .flat_map(|l| l.nodes.iter())
+            .filter(|n| n.children.is_empty())
+            .collect()
+    }
+
+    /// Get the leaf nodes among a node and its descendants, in [depth-first](SceneGraph::dfs)
+    /// order.
+    pub fn leaves_under(&self, root: usize) -> Result<Vec<&Node<T>>> {
+        self.node(root)?;
+        Ok(self.dfs(root).filter(|n| n.children.is_empty()).collect())
+    }
+
+    /// Get the number of leaf nodes among the descendants of a node, e.g. the number of
+    /// coordinate nodes making up an object. A leaf node has no descendants and counts 0.
+    pub fn descendant_leaf_count(&self, nid: usize) -> Result<usize> {
+        self.node(nid)?;
+        Ok(self
+            .dfs(nid)
+            .skip(1)
+            .filter(|n| n.children.is_empty())
+            .count())
+    }
+
+    /// Get the [`descendant_leaf_count`](SceneGraph::descendant_leaf_count) of every node on a
+    /// layer, keyed by node ID.
+    pub fn leaf_counts_for_layer(&self, index: usize) -> Result<HashMap<usize, usize>> {
+        self.layer(index)?;
+        // a single pass from the bottom layer up, where every child adds itself if it is a leaf
+        // along with its own count to its parent's
+        let mut counts = HashMap::new();
+        let mut contributions = HashMap::new();
+        for layer in &self.layers[..=index] {
+            counts = layer
+                .nodes
+                .iter()
+                .map(|n| {
+                    let count = n
+                        .children
+                        .iter()
+                        .filter_map(|cid| contributions.get(cid))
+                        .sum::<usize>();
+                    (n.id, count)
+                })
+                .collect::<HashMap<_, _>>();
+            contributions = layer
+                .nodes
+                .iter()
+                .map(|n| (n.id, counts[&n.id] + usize::from(n.children.is_empty())))
+                .collect();
+        }
+        Ok(counts)
+    }
+
+    /// Get the IDs of the nodes along a shortest path from `src` to `dst` on the layer of `src`,
+    /// see [`Layer::shortest_path`].
+    pub fn shortest_path(&self, src: usize, dst: usize) -> Result<Vec<usize>> {
+        self.layers[self.layer_of(src)?].shortest_path(src, dst)
+    }
+
+    /// Get the edges along a shortest path from `src` to `dst` on the layer of `src`, see
+    /// [`Layer::shortest_path_edges`].
+    pub fn shortest_path_edges(&self, src: usize, dst: usize) -> Result<Vec<Cow<'_, Edge>>> {
+        self.layers[self.layer_of(src)?].shortest_path_edges(src, dst)
+    }
+
+    /// Get the edges between the specified nodes, i.e. the edges of the subgraph induced by
+    /// them, indexed by layer.
+    pub fn induced_edges(&self, nodes: &HashSet<usize>) -> Vec<Vec<Cow<'_, Edge>>> {
+        self.layers
+            .iter()
+            .map(|l| l.edges_crossing(nodes, nodes))
+            .collect()
+    }
+
+    /// Get the edges among a node and its descendants, indexed by layer like the
+    /// [`subgraph`](SceneGraph::subgraph) rooted at the node, but borrowed instead of cloned.
+    /// Edges to nodes outside of the subtree are left out.
+    pub fn subtree_edges(&self, root: usize) -> Result<Vec<Vec<Cow<'_, Edge>>>> {
+        let root_layer_id = self.layer_of(root)?;
+        let subtree = self.dfs(root).map(|n| n.id).collect::<HashSet<_>>();
+        let mut edges = vec![Vec::new(); root_layer_id + 1];
+        for (depth, node) in self.bfs(root) {
+            let layer = &self.layers[root_layer_id - depth];
+            edges[root_layer_id - depth]
+                .extend(layer.out_edges(node).filter(|e| subtree.contains(&e.dst)));
+        }
+        Ok(edges)
+    }
 }
 
 /// Query
-impl SceneGraph {
+impl<T> SceneGraph<T> {
+    /// Get the IDs of the ancestors of a node, from its parent up to the root of its hierarchy.
+    pub fn ancestors(&self, nid: usize) -> Result<Vec<usize>> {
+        let mut ancestors = Vec::new();
+        let mut node = self.node(nid)?;
+        while let Some(pid) = node.pid {
+            // a well-formed hierarchy has at most one ancestor per layer
+            if ancestors.len() >= self.layers.len() {
+                return Err(AtlasError::CyclicNesting(nid, pid));
+            }
+            ancestors.push(pid);
+            node = self.node(pid)?;
+        }
+        Ok(ancestors)
+    }
+
+    /// Get the deepest node that is an ancestor of both nodes, or `None` if they are in
+    /// separate hierarchies. A node counts as its own ancestor here, so if `a` is an ancestor of
+    /// `b`, `a` is returned.
+    pub fn lowest_common_ancestor(&self, a: usize, b: usize) -> Result<Option<usize>> {
+        let b_chain = std::iter::once(b)
+            .chain(self.ancestors(b)?)
+            .collect::<HashSet<_>>();
+        Ok(std::iter::once(a)
+            .chain(self.ancestors(a)?)
+            .find(|id| b_chain.contains(id)))
+    }
+
+    /// Get the values of the feature `key` along the path from the root of a node's hierarchy
+    /// down to the node itself, e.g. for breadcrumbs. Nodes lacking the feature are skipped.
+    pub fn label_path(&self, nid: usize, key: &str) -> Result<Vec<String>> {
+        let mut path = Vec::new();
+        for id in std::iter::once(nid).chain(self.ancestors(nid)?) {
+            if let Ok(value) = self.node(id)?.feature(key) {
+                path.push(value.to_string());
+            }
+        }
+        path.reverse();
+        Ok(path)
+    }
+
+    /// Group the coordinate nodes of a layer that coincide, i.e. that are chained to each other
+    /// by coordinates at most `tol` apart. Only groups of at least two nodes are returned,
+    /// ordered like the nodes of the layer, e.g. to find duplicates produced by an import.
+    pub fn find_coincident(&self, layer: usize, tol: f32) -> Result<Vec<Vec<usize>>> {
+        let nodes = self
+            .layer(layer)?
+            .nodes
+            .iter()
+            .filter_map(|n| n.coordinates.map(|c| (n.id, c)))
+            .collect::<Vec<_>>();
+
+        // union-find over the positions in `nodes`
+        let mut parent = (0..nodes.len()).collect::<Vec<_>>();
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for i in 0..nodes.len() {
+            for j in i + 1..nodes.len() {
+                if nodes[i].1.distance(nodes[j].1) <= tol {
+                    let (ri, rj) = (find(&mut parent, i), find(&mut parent, j));
+                    parent[ri.max(rj)] = ri.min(rj);
+                }
+            }
+        }
+
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_of = HashMap::new();
+        for (i, (nid, _)) in nodes.iter().enumerate() {
+            let root = find(&mut parent, i);
+            let g = *group_of.entry(root).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[g].push(*nid);
+        }
+        groups.retain(|g| g.len() > 1);
+        Ok(groups)
+    }
+
+    /// Get the IDs of the `n` nodes updated by the most merges, most updated first, see
+    /// [`Node::update_count`]. Ties are broken by ID.
+    pub fn most_updated_nodes(&self, n: usize) -> Vec<usize> {
+        let mut nodes = self
+            .layers
+            .iter()
+            .flat_map(|l| l.nodes.iter())
+            .map(|node| (node.update_count, node.id))
+            .collect::<Vec<_>>();
+        nodes.sort_by(|(c1, id1), (c2, id2)| c2.cmp(c1).then(id1.cmp(id2)));
+        nodes.into_iter().take(n).map(|(_, id)| id).collect()
+    }
+
+    /// Get the `(parent, child)` pairs of the children entries pointing to nodes that do not
+    /// exist on the layer below the parent. Unlike
+    /// [`repair_hierarchy`](SceneGraph::repair_hierarchy), nothing is fixed.
+    pub fn orphaned_child_refs(&self) -> Vec<(usize, usize)> {
+        let mut orphaned = Vec::new();
+        let mut below = HashSet::new();
+        for layer in &self.layers {
+            for node in &layer.nodes {
+                orphaned.extend(
+                    node.children
+                        .iter()
+                        .filter(|cid| !below.contains(*cid))
+                        .map(|&cid| (node.id, cid)),
+                );
+            }
+            below = layer.nodes.iter().map(|n| n.id).collect();
+        }
+        orphaned
+    }
+
     /// Get List of all nodes having a specific set of features.
-    pub fn nodes_having(&self, keys: &[&str]) -> Vec<Vec<&Node>> {
+    pub fn nodes_having(&self, keys: &[&str]) -> Vec<Vec<&Node<T>>> {
         self.layers.iter().map(|l| l.nodes_having(keys)).collect()
     }
 
+    /// Get List of all nodes lacking the feature `key`.
+    pub fn nodes_missing(&self, key: &str) -> Vec<Vec<&Node<T>>> {
+        self.layers.iter().map(|l| l.nodes_missing(key)).collect()
+    }
+
+    /// Get List of all nodes having at least `n` features.
+    pub fn nodes_with_min_features(&self, n: usize) -> Vec<Vec<&Node<T>>> {
+        self.layers
+            .iter()
+            .map(|l| l.nodes_with_min_features(n))
+            .collect()
+    }
+
+    /// Get List of all nodes having a numeric feature `key` whose value is within `[min, max]`.
+    /// Feature values that do not parse as numbers are ignored.
+    pub fn nodes_with_numeric_feature(&self, key: &str, min: f64, max: f64) -> Vec<Vec<&Node<T>>> {
+        self.layers
+            .iter()
+            .map(|l| l.nodes_with_numeric_feature(key, min, max))
+            .collect()
+    }
+
     /// Get List of all nodes matching a specific set of features.
-    pub fn nodes_matching(&self, features: &[&Feature]) -> Vec<Vec<&Node>> {
+    pub fn nodes_matching(&self, features: &[&Feature]) -> Vec<Vec<&Node<T>>> {
         self.layers
             .iter()
             .map(|l| l.nodes_matching(features))
             .collect()
     }
 
+    /// Get the number of occurrences of every distinct feature key-value pair across all layers.
+    pub fn feature_histogram(&self) -> HashMap<(String, String), usize> {
+        let mut histogram = HashMap::new();
+        self.layers
+            .iter()
+            .flat_map(|l| l.nodes.iter())
+            .flat_map(|n| n.features.iter())
+            .for_each(|f| {
+                *histogram
+                    .entry((f.key().to_string(), f.value().to_string()))
+                    .or_insert(0) += 1;
+            });
+        histogram
+    }
+
+    /// Get the number of edges with every distinct description across all layers.
+    pub fn edge_desc_histogram(&self) -> HashMap<String, usize> {
+        let mut histogram = HashMap::new();
+        self.layers
+            .iter()
+            .flat_map(|l| l.all_edges())
+            .for_each(|e| *histogram.entry(e.desc.clone()).or_insert(0) += 1);
+        histogram
+    }
+
+    /// Get the IDs of the [isolated nodes](Layer::isolated_nodes) of every layer, indexed by layer.
+    pub fn isolated_nodes_per_layer(&self) -> Vec<Vec<usize>> {
+        self.layers.iter().map(|l| l.isolated_nodes()).collect()
+    }
+
     /// Get a subgraph containing nodes within the field of view of an observer and are descendants of the specified root node.
-    /// The check is done using the nodes' coordinates and nodes without coordinates are pruned.
+    /// The check is done using the nodes' representative point (see
+    /// [`Layer::observable_nodes`]) and nodes without one are pruned.
     /// nodes from upper layers that have no descendants within the field of view are also pruned.
-    pub fn visible_subgraph(&self, observer: Observer, root_node_id: usize) -> Result<Self> {
-        let subgraph_layers = self.subgraph(root_node_id)?.layers;
+    pub fn visible_subgraph(&self, observer: Observer, root_node_id: usize) -> Result<Self>
+    where
+        T: Clone,
+    {
+        self.culled_subgraph(root_node_id, usize::MAX, |p| observer.observers(p))
+    }
 
-        if subgraph_layers.is_empty() {
-            return Ok(Default::default());
-        }
-        let first_layer = subgraph_layers[0].observable_nodes(observer);
+    /// Get a subgraph like [`visible_subgraph`](SceneGraph::visible_subgraph) that only keeps
+    /// the coordinate nodes that are also within `radius` of the observer position.
+    pub fn visible_within_radius(
+        &self,
+        observer: Observer,
+        root_node_id: usize,
+        radius: f32,
+    ) -> Result<Self>
+    where
+        T: Clone,
+    {
+        self.culled_subgraph(root_node_id, usize::MAX, |p| {
+            observer.observers(p) && observer.distance(p) <= radius
+        })
+    }
+
+    /// Get a subgraph like [`visible_subgraph`](SceneGraph::visible_subgraph) that only keeps
+    /// the coordinate nodes within the field of view of every observer, e.g. the overlap of a
+    /// stereo pair. With no observers, every node with a representative point is kept.
+    pub fn visible_subgraph_all(&self, observers: &[Observer], root_node_id: usize) -> Result<Self>
+    where
+        T: Clone,
+    {
+        self.culled_subgraph(root_node_id, usize::MAX, |p| {
+            observers.iter().all(|o| o.observers(p))
+        })
+    }
+
+    /// Get a subgraph like [`visible_subgraph`](SceneGraph::visible_subgraph) that only descends
+    /// `max_depth` layers below the root node and culls the nodes of the lowest layer reached,
+    /// without building the rest of the subtree. Layers below it are left empty.
+    /// Nodes are culled by their representative point, so e.g. semantic nodes need coordinates
+    /// or a point cloud to be visible.
+    pub fn visible_subgraph_depth_limited(
+        &self,
+        observer: Observer,
+        root_node_id: usize,
+        max_depth: usize,
+    ) -> Result<Self>
+    where
+        T: Clone,
+    {
+        self.culled_subgraph(root_node_id, max_depth, |p| observer.observers(p))
+    }
+
+    /// Get the subgraph under the specified root node, descending at most `max_depth` layers,
+    /// keeping only the nodes of the lowest layer reached whose representative point passes
+    /// `keep`, and the nodes from upper layers that have descendants among them.
+    fn culled_subgraph(
+        &self,
+        root_node_id: usize,
+        max_depth: usize,
+        keep: impl Fn(&Coordinate) -> bool,
+    ) -> Result<Self>
+    where
+        T: Clone,
+    {
+        let mut layers = self.subgraph_depth_limited(root_node_id, max_depth)?.layers;
+        let bottom = (layers.len() - 1).saturating_sub(max_depth);
+
+        let culled_layer = &mut layers[bottom];
+        let kept = culled_layer
+            .nodes
+            .iter()
+            .filter(|n| n.representative_point().is_some_and(|p| keep(&p)))
+            .map(|n| n.id)
+            .collect::<Vec<_>>();
+        culled_layer.retain_nodes(&kept);
 
-        let mut retain_nodes = first_layer
+        let mut retain_nodes = culled_layer
             .nodes
             .iter()
             .filter_map(|n| n.pid)
@@ -268,10 +1664,8 @@ impl SceneGraph {
             .into_iter()
             .collect::<Vec<_>>();
 
-        let mut layers = vec![first_layer];
-
-        for mut layer in subgraph_layers.into_iter().skip(1) {
-            layer.retain_nodes(&retain_nodes.into_iter().collect::<Vec<_>>());
+        for layer in layers.iter_mut().skip(bottom + 1) {
+            layer.retain_nodes(&retain_nodes);
             retain_nodes = layer
                 .nodes
                 .iter()
@@ -279,29 +1673,354 @@ impl SceneGraph {
                 .collect::<HashSet<_>>()
                 .into_iter()
                 .collect::<Vec<_>>();
-            layers.push(layer);
         }
         Ok(Self {
             node_counter: self.node_counter,
+            derived_at: Some(self.node_counter),
             layers,
+            cross_edges: Vec::new(),
+            generation: 0,
+            index: NodeIndex::default(),
         })
     }
 
+    /// Get the edges of the [`visible_subgraph`](SceneGraph::visible_subgraph) under the
+    /// specified root node, indexed by layer like [`subtree_edges`](SceneGraph::subtree_edges),
+    /// borrowed instead of cloned.
+    pub fn visible_edges(
+        &self,
+        observer: Observer,
+        root_node_id: usize,
+    ) -> Result<Vec<Vec<Cow<'_, Edge>>>> {
+        let subtree = self.bfs(root_node_id).collect::<Vec<_>>();
+        // the bottom layer is culled, like in `visible_subgraph`, even if the subtree does not
+        // reach it
+        let bottom = self.layer_of(root_node_id)?;
+
+        // observed nodes of the bottom layer, and their ancestors up to the root
+        let mut visible = HashSet::new();
+        let mut ancestors = HashMap::new();
+        for (depth, node) in &subtree {
+            if *depth < bottom {
+                ancestors.insert(node.id, node.pid);
+            } else if node
+                .representative_point()
+                .is_some_and(|p| observer.observers(&p))
+            {
+                let mut pid = node.pid;
+                visible.insert(node.id);
+                while let Some(id) = pid.filter(|id| ancestors.contains_key(id)) {
+                    if !visible.insert(id) {
+                        break;
+                    }
+                    pid = ancestors[&id];
+                }
+            }
+        }
+
+        let mut edges = self.subtree_edges(root_node_id)?;
+        for layer in &mut edges {
+            layer.retain(|e| visible.contains(&e.src) && visible.contains(&e.dst));
+        }
+        Ok(edges)
+    }
+
+    /// Count the coordinate nodes under the specified root node that are within the observer's
+    /// field of view, i.e. the nodes in the bottom layer of the
+    /// [`visible_subgraph`](SceneGraph::visible_subgraph), without cloning any nodes.
+    pub fn count_visible(&self, observer: Observer, root_node_id: usize) -> Result<usize> {
+        let root_layer_id = self.layer_of(root_node_id)?;
+        let count = self
+            .bfs(root_node_id)
+            .filter(|(depth, _)| *depth == root_layer_id)
+            .filter(|(_, n)| {
+                n.representative_point()
+                    .is_some_and(|p| observer.observers(&p))
+            })
+            .count();
+        Ok(count)
+    }
+
+    /// Estimate the fraction of the bounding box of the nodes under the specified root node that
+    /// lies within the observer's field of view.
+    /// The box spans the representative points of all nodes in the subtree and is sampled on a
+    /// regular grid, so the result is approximate. Along axes where the box is thinner than the
+    /// near distance of the observer, e.g. for coplanar nodes or a single node, it is padded to
+    /// that thickness around the nodes so that the samples do not collapse onto them.
+    /// Returns `0.0` if no node in the subtree is positioned.
+    pub fn coverage_ratio(&self, observer: Observer, root_node_id: usize) -> Result<f32> {
+        const SAMPLES: usize = 16;
+
+        self.layer_of(root_node_id)?;
+        let Some((min, max)) = self
+            .bfs(root_node_id)
+            .filter_map(|(_, n)| n.representative_point())
+            .fold(None, |bounds: Option<(Coordinate, Coordinate)>, p| {
+                Some(bounds.map_or((p, p), |(min, max)| (min.min(p), max.max(p))))
+            })
+        else {
+            return Ok(0.0);
+        };
+        let thickness = Coordinate::splat(observer.near().max(f32::EPSILON));
+        let pad = (thickness - (max - min)).max(Coordinate::ZERO) / 2.0;
+        let (min, max) = (min - pad, max + pad);
+
+        let step = (max - min) / SAMPLES as f32;
+        let mut covered = 0;
+        for i in 0..SAMPLES {
+            for j in 0..SAMPLES {
+                for k in 0..SAMPLES {
+                    let cell = Coordinate::new(i as f32, j as f32, k as f32) + 0.5;
+                    if observer.observers(&(min + cell * step)) {
+                        covered += 1;
+                    }
+                }
+            }
+        }
+        Ok(covered as f32 / SAMPLES.pow(3) as f32)
+    }
+
+    /// Get the IDs of the coordinate nodes under the specified root node that are
+    /// [on the boundary](Observer::on_boundary) of the observer's field of view, i.e. observed
+    /// nodes that are about to leave the view.
+    pub fn boundary_nodes(
+        &self,
+        observer: Observer,
+        root_node_id: usize,
+        angular_tol: f32,
+        dist_tol: f32,
+    ) -> Result<Vec<usize>> {
+        let root_layer_id = self.layer_of(root_node_id)?;
+        let nodes = self
+            .bfs(root_node_id)
+            .filter(|(depth, _)| *depth == root_layer_id)
+            .filter(|(_, n)| {
+                n.representative_point()
+                    .is_some_and(|p| observer.on_boundary(&p, angular_tol, dist_tol))
+            })
+            .map(|(_, n)| n.id)
+            .collect();
+        Ok(nodes)
+    }
+
+    /// Check whether two observers cover the same nodes under the specified root node, i.e.
+    /// whether their [`visible_subgraph`](SceneGraph::visible_subgraph)s hold the same node IDs.
+    pub fn coverage_equivalent(
+        &self,
+        a: &Observer,
+        b: &Observer,
+        root_node_id: usize,
+    ) -> Result<bool>
+    where
+        T: Clone,
+    {
+        Ok(self.visible_ids(a, root_node_id)? == self.visible_ids(b, root_node_id)?)
+    }
+
+    /// Get the nodes under the specified root node that entered and left the field of view when
+    /// moving from one observer pose to another, as `(newly visible, newly hidden)` sorted IDs.
+    /// Nodes of every layer of the [`visible_subgraph`](SceneGraph::visible_subgraph) count.
+    pub fn visibility_delta(
+        &self,
+        root_node_id: usize,
+        from: &Observer,
+        to: &Observer,
+    ) -> Result<(Vec<usize>, Vec<usize>)>
+    where
+        T: Clone,
+    {
+        let before = self.visible_ids(from, root_node_id)?;
+        let after = self.visible_ids(to, root_node_id)?;
+        let mut entered = after.difference(&before).copied().collect::<Vec<_>>();
+        let mut left = before.difference(&after).copied().collect::<Vec<_>>();
+        entered.sort_unstable();
+        left.sort_unstable();
+        Ok((entered, left))
+    }
+
+    /// Get the `(src, dst)` pairs of the edges under the specified root node that the
+    /// [`visible_subgraph`](SceneGraph::visible_subgraph) drops because at least one of their
+    /// endpoints was culled, ordered from the bottom layer up.
+    pub fn clipped_edges(
+        &self,
+        observer: Observer,
+        root_node_id: usize,
+    ) -> Result<Vec<(usize, usize)>>
+    where
+        T: Clone,
+    {
+        let visible = self.visible_ids(&observer, root_node_id)?;
+        Ok(self
+            .subtree_edges(root_node_id)?
+            .into_iter()
+            .flatten()
+            .filter(|e| !visible.contains(&e.src) || !visible.contains(&e.dst))
+            .map(|e| (e.src, e.dst))
+            .collect())
+    }
+
+    /// Get the IDs of the nodes of the [`visible_subgraph`](SceneGraph::visible_subgraph).
+    fn visible_ids(&self, observer: &Observer, root_node_id: usize) -> Result<HashSet<usize>>
+    where
+        T: Clone,
+    {
+        let visible = self.visible_subgraph(*observer, root_node_id)?;
+        Ok(visible
+            .layers
+            .iter()
+            .flat_map(|l| l.nodes.iter().map(|n| n.id))
+            .collect())
+    }
+
+    /// Get the IDs of the observed nodes under the specified root node, ordered near-to-far
+    /// by their [`depth`](Observer::depth) with respect to the observer.
+    pub fn visible_sorted_by_depth(
+        &self,
+        observer: Observer,
+        root_node_id: usize,
+    ) -> Result<Vec<usize>>
+    where
+        T: Clone,
+    {
+        let visible = self.visible_subgraph(observer, root_node_id)?;
+        let mut nodes = match visible.layers.first() {
+            Some(l) => l
+                .nodes
+                .iter()
+                .filter_map(|n| n.representative_point().map(|p| (n.id, observer.depth(&p))))
+                .collect::<Vec<_>>(),
+            None => return Ok(Vec::new()),
+        };
+        nodes.sort_by(|(_, d1), (_, d2)| d1.total_cmp(d2));
+        Ok(nodes.into_iter().map(|(id, _)| id).collect())
+    }
+
+    /// Get the observed nodes under the specified root node along with their representative
+    /// point in the observer's [local frame](Observer::to_local), e.g. for ego-centric rendering.
+    pub fn visible_local(
+        &self,
+        observer: Observer,
+        root_node_id: usize,
+    ) -> Result<Vec<(usize, Coordinate)>>
+    where
+        T: Clone,
+    {
+        let visible = self.visible_subgraph(observer, root_node_id)?;
+        let nodes = visible.layers.first().map_or_else(Vec::new, |l| {
+            l.nodes
+                .iter()
+                .filter_map(|n| {
+                    n.representative_point()
+                        .map(|p| (n.id, observer.to_local(&p)))
+                })
+                .collect()
+        });
+        Ok(nodes)
+    }
+
+    /// Get the IDs of the `k` observed nodes under the specified root node closest to the
+    /// observer, ordered near-to-far like
+    /// [`visible_sorted_by_depth`](SceneGraph::visible_sorted_by_depth).
+    pub fn k_nearest_visible(
+        &self,
+        observer: Observer,
+        root_node_id: usize,
+        k: usize,
+    ) -> Result<Vec<usize>>
+    where
+        T: Clone,
+    {
+        let mut nodes = self.visible_sorted_by_depth(observer, root_node_id)?;
+        nodes.truncate(k);
+        Ok(nodes)
+    }
+
+    /// Get the ID of the observed node under the specified root node with the smallest
+    /// [`depth`](Observer::depth) with respect to the observer, or `None` if nothing is visible.
+    pub fn nearest_observable(
+        &self,
+        observer: Observer,
+        root_node_id: usize,
+    ) -> Result<Option<usize>>
+    where
+        T: Clone,
+    {
+        let visible = self.visible_subgraph(observer, root_node_id)?;
+        let nearest = visible.layers.first().and_then(|l| {
+            l.nodes
+                .iter()
+                .filter_map(|n| n.representative_point().map(|p| (n.id, observer.depth(&p))))
+                .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+                .map(|(id, _)| id)
+        });
+        Ok(nearest)
+    }
+
+    /// Get the indices of the observers that observe a node.
+    /// Returns an error if the node has neither coordinates nor a point cloud.
+    pub fn observers_of(&self, nid: usize, observers: &[Observer]) -> Result<Vec<usize>> {
+        let point = self
+            .node(nid)?
+            .representative_point()
+            .ok_or(AtlasError::CoordinatesRequired)?;
+        Ok(observers
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.observers(&point))
+            .map(|(i, _)| i)
+            .collect())
+    }
+
+    /// Get the visibility of every node having coordinates or a point cloud, across all layers.
+    /// Unlike [`visible_subgraph`](SceneGraph::visible_subgraph), the hierarchy is ignored and
+    /// nothing is pruned: each node is answered for on its own.
+    /// Nodes are checked using their [representative point](Node::representative_point), and
+    /// nodes without one are not part of the map.
+    pub fn visibility_map(&self, observer: Observer) -> HashMap<usize, bool> {
+        self.layers
+            .iter()
+            .flat_map(|l| l.nodes.iter())
+            .filter_map(|n| {
+                n.representative_point()
+                    .map(|p| (n.id, observer.observers(&p)))
+            })
+            .collect()
+    }
+
     /// Get List of all edges matching a specific description.
-    pub fn edges_matching(&self, desc: &str) -> Vec<Vec<&Edge>> {
+    pub fn edges_matching(&self, desc: &str) -> Vec<Vec<Cow<'_, Edge>>> {
         self.layers.iter().map(|l| l.edges_matching(desc)).collect()
     }
 
     /// Get List of all edges from a specific source node.
-    pub fn edges_from(&self, src: usize) -> Vec<&Edge> {
-        match self.node(src) {
-            Ok(n) => n.edges.iter().collect(),
-            Err(_) => Vec::new(),
+    pub fn edges_from(&self, src: usize) -> Vec<Cow<'_, Edge>> {
+        match self.locate(src) {
+            Some((lid, pos)) => {
+                let layer = &self.layers[lid];
+                layer.out_edges(&layer.nodes[pos]).collect()
+            }
+            None => Vec::new(),
         }
     }
 
+    /// Get List of all cross edges from a specific source node.
+    pub fn cross_edges_from(&self, src: usize) -> Vec<&(usize, usize, String)> {
+        self.cross_edges
+            .iter()
+            .filter(|(s, _, _)| *s == src)
+            .collect()
+    }
+
+    /// Get List of all edges from a specific source node matching a specific description.
+    pub fn edges_from_matching(&self, src: usize, desc: &str) -> Vec<Cow<'_, Edge>> {
+        self.edges_from(src)
+            .into_iter()
+            .filter(|e| e.desc == desc)
+            .collect()
+    }
+
     /// Get List of all edges to a specific destination node.
-    pub fn edges_to(&self, dst: usize) -> Vec<&Edge> {
+    pub fn edges_to(&self, dst: usize) -> Vec<Cow<'_, Edge>> {
         self.layers.iter().flat_map(|l| l.edges_to(dst)).collect()
     }
 }
@@ -310,21 +2029,21 @@ impl SceneGraph {
 /// Refer to the `nest` method in `SceneGraph` for usage example.
 ///
 /// [`nest`](SceneGraph::nest)
-pub struct NestUnder<'a> {
-    sg: &'a mut SceneGraph,
+pub struct NestUnder<'a, T = ()> {
+    sg: &'a mut SceneGraph<T>,
     nestee: usize,
 }
 
-impl<'a> NestUnder<'a> {
+impl<'a, T> NestUnder<'a, T> {
     /// Complete the nesting operation by specifying the `nester` node under which the `nestee` node
     /// Refer to the `nest` method in `SceneGraph` for usage example.
     ///
     /// [`nest`](SceneGraph::nest)
-    pub fn under(&mut self, nester: usize) -> Result<&mut SceneGraph> {
+    pub fn under(&mut self, nester: usize) -> Result<&mut SceneGraph<T>> {
         let nester_layer_id = self.sg.layer_of(nester)?;
         let nestee_layer_id = self.sg.layer_of(self.nestee)?;
 
-        if nester_layer_id - 1 != nestee_layer_id {
+        if nester_layer_id.checked_sub(1) != Some(nestee_layer_id) {
             return Err(AtlasError::InvalidLayersForNesting(
                 nestee_layer_id,
                 nester_layer_id,
@@ -334,11 +2053,11 @@ impl<'a> NestUnder<'a> {
         let nestee = self.sg.node_mut(self.nestee)?;
         match nestee.pid {
             // Remove from old parent
-            Some(parent_id) => {
+            Some(parent_id) if parent_id != nester => {
                 nestee.pid = Some(nester);
                 self.sg.node_mut(parent_id)?.remove_child(self.nestee)?;
             }
-            None => nestee.pid = Some(nester),
+            _ => nestee.pid = Some(nester),
         }
 
         self.sg.node_mut(nester)?.add_child(self.nestee);
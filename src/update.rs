@@ -3,7 +3,10 @@ use crate::sg::SceneGraph;
 
 #[derive(Default)]
 pub struct UpdatePipeline {
-    update_queue: Vec<SceneGraph>,
+    /// Pending updates tagged with their sequence number.
+    update_queue: Vec<(u64, SceneGraph)>,
+    /// Sequence number of the next pushed update.
+    next_seq: u64,
 }
 
 impl UpdatePipeline {
@@ -13,16 +16,69 @@ impl UpdatePipeline {
 
     /// Push a new scene graph update to the pipeline for Lazy evaluation.
     /// It is guaranteed that the updates will be applied in the order they were pushed.
-    pub fn push(&mut self, scene_graph: SceneGraph) {
+    /// Returns the sequence number of the update, see [`flush_until`](UpdatePipeline::flush_until).
+    pub fn push(&mut self, scene_graph: SceneGraph) -> u64 {
         // todo: develop some mechanism for seperating conflict-free updates
-        self.update_queue.push(scene_graph);
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.update_queue.push((seq, scene_graph));
+        seq
     }
 
     pub fn flush<'a>(&mut self, sg: &'a mut SceneGraph) -> Result<&'a mut SceneGraph> {
-        let updates = std::mem::take(&mut self.update_queue);
+        self.flush_until(sg, u64::MAX)
+    }
+
+    /// Apply the pending updates with a sequence number up to `seq` (inclusive), in order,
+    /// leaving later updates queued.
+    pub fn flush_until<'a>(
+        &mut self,
+        sg: &'a mut SceneGraph,
+        seq: u64,
+    ) -> Result<&'a mut SceneGraph> {
+        let split = self.update_queue.partition_point(|(s, _)| *s <= seq);
+        let updates = self.update_queue.drain(..split).collect::<Vec<_>>();
 
         // todo: First resolve conflicts between updates and then apply the final sub-graph to the main scene graph
-        updates.into_iter().try_for_each(|u| sg.merge(u))?;
+        updates
+            .into_iter()
+            .try_for_each(|(_, u)| sg.merge(u).map(|_| ()))?;
         Ok(sg)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flush_until() -> Result<()> {
+        let mut pipeline = UpdatePipeline::new();
+        let mut sg = SceneGraph::default();
+        sg.new_layer();
+
+        let mut seqs = Vec::new();
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let mut update = sg.clone();
+            let id = update.spawn_node(0, vec![])?;
+            // clones of the same graph hand out the same IDs, which would be merged together
+            sg.new_node(vec![]);
+            ids.push(id);
+            seqs.push(pipeline.push(update));
+        }
+        assert_eq!(seqs, [0, 1, 2]);
+
+        pipeline.flush_until(&mut sg, seqs[1])?;
+        assert!(sg.node(ids[0]).is_ok() && sg.node(ids[1]).is_ok());
+        assert!(sg.node(ids[2]).is_err());
+
+        // flushing an already applied range is a no-op, the rest is still queued
+        pipeline.flush_until(&mut sg, seqs[1])?;
+        assert!(sg.node(ids[2]).is_err());
+        pipeline.flush(&mut sg)?;
+        assert!(sg.node(ids[2]).is_ok());
+
+        Ok(())
+    }
+}